@@ -0,0 +1,253 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::normalize::normalize;
+
+/// Artists whose name contains something that otherwise separates artists.
+const KNOWN_ARTISTS: [&str; 26] = [
+    "ac/dc",
+    "simon & garfunkel",
+    "earth, wind & fire",
+    "crosby, stills, nash & young",
+    "crosby, stills & nash",
+    "hall & oates",
+    "daryl hall & john oates",
+    "emerson, lake & palmer",
+    "peter, paul and mary",
+    "tyler, the creator",
+    "mumford & sons",
+    "kool & the gang",
+    "sly & the family stone",
+    "derek & the dominos",
+    "ike & tina turner",
+    "bob marley & the wailers",
+    "tom petty & the heartbreakers",
+    "prince & the revolution",
+    "marina & the diamonds",
+    "florence + the machine",
+    "above & beyond",
+    "chase & status",
+    "she & him",
+    "matt & kim",
+    "brooks & dunn",
+    "big & rich",
+];
+
+/// "(feat. x)", "[with x]" and the like anywhere in a title.
+static TITLE_FEATURE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\s*[\(\[](?:feat\.?|ft\.?|featuring|with)\s+([^\)\]]+)[\)\]]").unwrap()
+});
+/// "song feat. x" or "song - feat. x", "with" isn't a feature when it isn't in brackets.
+static TRAILING_FEATURE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s*(?:-\s*)?\b(?:feat\.?|ft\.?|featuring)\s+(.+)$").unwrap());
+/// The start of the featured artists in an artist field.
+static ARTIST_FEATURE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s*[\(\[]?\b(?:feat\.?|ft\.?|featuring|with)(?:\s+|$)").unwrap());
+static ARTIST_SEPARATOR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s*(?:[,&/;]|\s+x\s+|\s+vs\.?\s+)\s*").unwrap());
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArtistCredit {
+    pub primary: Vec<String>,
+    pub featured: Vec<String>,
+}
+
+impl ArtistCredit {
+    /// Adds the artists of `other`, an artist already credited as primary stays primary.
+    pub fn merge(&mut self, other: ArtistCredit) {
+        for artist in other.primary {
+            self.featured.retain(|a| *a != artist);
+            if !self.primary.contains(&artist) {
+                self.primary.push(artist);
+            }
+        }
+        for artist in other.featured {
+            if !self.primary.contains(&artist) && !self.featured.contains(&artist) {
+                self.featured.push(artist);
+            }
+        }
+    }
+}
+
+pub struct ArtistCreditParser {
+    /// Known artists containing a separator, longest first so that e.g. "crosby, stills,
+    /// nash & young" is protected before "crosby, stills & nash".
+    known: Vec<String>,
+}
+
+impl ArtistCreditParser {
+    /// A parser that also keeps `artists` (e.g. the artists of the plex library) together.
+    pub fn new<I, S>(artists: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut known: Vec<String> = KNOWN_ARTISTS
+            .iter()
+            .map(|a| a.to_string())
+            .chain(artists.into_iter().map(|a| normalize(a.as_ref())))
+            .filter(|a| ARTIST_SEPARATOR.is_match(a) || ARTIST_FEATURE.is_match(a))
+            .collect();
+        // dedup only removes neighbours, sort by name first.
+        known.sort();
+        known.dedup();
+        known.sort_by_key(|a| std::cmp::Reverse(a.len()));
+        Self { known }
+    }
+
+    fn split(&self, artists: &str) -> Vec<String> {
+        // swap known names for placeholders so their separators are left alone.
+        let mut protected = artists.to_string();
+        let mut replaced: Vec<&String> = vec![];
+        for known in self.known.iter() {
+            if protected.contains(known.as_str()) {
+                protected = protected.replace(
+                    known.as_str(),
+                    &format!("\u{e000}{}\u{e001}", replaced.len()),
+                );
+                replaced.push(known);
+            }
+        }
+
+        ARTIST_SEPARATOR
+            .split(&protected)
+            .map(|a| {
+                let mut artist = a
+                    .trim_matches(|c: char| c.is_whitespace() || c == ')' || c == ']')
+                    .to_string();
+                for (i, known) in replaced.iter().enumerate() {
+                    artist = artist.replace(&format!("\u{e000}{}\u{e001}", i), known);
+                }
+                artist
+            })
+            .filter(|a| !a.is_empty())
+            .collect()
+    }
+
+    /// Parses an artist field such as "a & b feat. c" into primary and featured artists.
+    pub fn parse_artists(&self, artists: &str) -> ArtistCredit {
+        let artists = normalize(artists);
+        // a known artist like "she & him" mustn't be cut at "with".
+        let feature_start = ARTIST_FEATURE
+            .find_iter(&artists)
+            .find(|m| {
+                !self.known.iter().any(|k| {
+                    artists
+                        .match_indices(k.as_str())
+                        .any(|(i, _)| i <= m.start() && m.end() <= i + k.len())
+                })
+            })
+            .map(|m| (m.start(), m.end()));
+
+        match feature_start {
+            Some((start, end)) => ArtistCredit {
+                primary: self.split(&artists[..start]),
+                featured: self.split(&artists[end..]),
+            },
+            None => ArtistCredit {
+                primary: self.split(&artists),
+                featured: vec![],
+            },
+        }
+    }
+
+    /// Removes the featured artists from a title, "song (feat. a & b)" => ("song", [a, b]).
+    pub fn parse_title(&self, title: &str) -> (String, Vec<String>) {
+        let title = normalize(title);
+        let mut featured = vec![];
+        for captures in TITLE_FEATURE.captures_iter(&title) {
+            featured.extend(self.split(&captures[1]));
+        }
+        let stripped = TITLE_FEATURE.replace_all(&title, "").to_string();
+        if let Some(captures) = TRAILING_FEATURE.captures(&stripped) {
+            featured.extend(self.split(&captures[1]));
+        }
+        let stripped = TRAILING_FEATURE.replace(&stripped, "").trim().to_string();
+
+        // a title can't be only the featured artists.
+        if stripped.is_empty() {
+            return (title, vec![]);
+        }
+        (stripped, featured)
+    }
+}
+
+impl Default for ArtistCreditParser {
+    fn default() -> Self {
+        Self::new(std::iter::empty::<&str>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_artists() {
+        let parser = ArtistCreditParser::new(["Tyler, The Creator"]);
+        let cases: [(&str, &[&str], &[&str]); 12] = [
+            ("Simon & Garfunkel", &["simon & garfunkel"], &[]),
+            ("AC/DC", &["ac/dc"], &[]),
+            ("Earth, Wind & Fire", &["earth, wind & fire"], &[]),
+            ("Drake, Future", &["drake", "future"], &[]),
+            ("Jay-Z & Kanye West", &["jay-z", "kanye west"], &[]),
+            (
+                "Calvin Harris feat. Rihanna",
+                &["calvin harris"],
+                &["rihanna"],
+            ),
+            ("A ft. B & C", &["a"], &["b", "c"]),
+            ("A featuring B", &["a"], &["b"]),
+            ("A (with B)", &["a"], &["b"]),
+            ("Skrillex x Diplo", &["skrillex", "diplo"], &[]),
+            ("Armin vs. Tiësto", &["armin", "tiesto"], &[]),
+            (
+                "Tyler, The Creator feat. Frank Ocean",
+                &["tyler, the creator"],
+                &["frank ocean"],
+            ),
+        ];
+        for (input, primary, featured) in cases {
+            let credit = parser.parse_artists(input);
+            assert_eq!(credit.primary, primary, "primary of {:?}", input);
+            assert_eq!(credit.featured, featured, "featured of {:?}", input);
+        }
+        assert_eq!(parser.parse_artists("She & Him").primary, ["she & him"]);
+
+        // also in the built in list and twice in the library.
+        let parser = ArtistCreditParser::new(["AC/DC", "Matt & Kim", "ac/dc"]);
+        let unique: std::collections::HashSet<&String> = parser.known.iter().collect();
+        assert_eq!(unique.len(), parser.known.len());
+    }
+
+    #[test]
+    fn test_parse_title() {
+        let parser = ArtistCreditParser::default();
+        let cases: [(&str, &str, &[&str]); 8] = [
+            ("Song", "song", &[]),
+            ("Song (feat. Rihanna)", "song", &["rihanna"]),
+            ("Song [ft. A & B]", "song", &["a", "b"]),
+            (
+                "Song (with Simon & Garfunkel)",
+                "song",
+                &["simon & garfunkel"],
+            ),
+            ("Song feat. A", "song", &["a"]),
+            ("Song - feat. A", "song", &["a"]),
+            ("Song (feat. A) - Remix", "song - remix", &["a"]),
+            ("Dancing with Myself", "dancing with myself", &[]),
+        ];
+        for (input, title, featured) in cases {
+            assert_eq!(
+                parser.parse_title(input),
+                (
+                    title.to_string(),
+                    featured.iter().map(|f| f.to_string()).collect()
+                ),
+                "{:?}",
+                input
+            );
+        }
+    }
+}
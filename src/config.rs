@@ -0,0 +1,28 @@
+use serde::Deserialize;
+
+use crate::plex::matcher::registry::MatcherSpec;
+
+/// Settings read from the `--config` json file.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Matchers tried in this order, the built in pipeline when not set.
+    pub matchers: Option<Vec<MatcherSpec>>,
+    /// Playlists synced by the `watch` command.
+    pub playlists: Vec<PlaylistConfig>,
+}
+
+/// A spotify playlist and the name of the plex playlist it is synced to.
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PlaylistConfig {
+    pub id: String,
+    pub name: String,
+}
+
+impl Config {
+    pub fn load(path: &str) -> Result<Self, anyhow::Error> {
+        let file = std::fs::File::open(path)?;
+        Ok(serde_json::from_reader(file)?)
+    }
+}
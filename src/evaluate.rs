@@ -0,0 +1,190 @@
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::{
+    plex::matcher::{
+        assignment, explain::MatchExplanation, index::PlexIndex, pipeline::Pipeline, MatchConfig,
+        MatchResult,
+    },
+    track_album_artist::{MetadataType, TrackAlbumArtist},
+};
+
+/// A spotify track and the rating key of the plex track it should match, none when
+/// the library doesn't have it.
+#[derive(Deserialize)]
+pub struct Case {
+    pub spotify: TrackAlbumArtist,
+    pub expected: Option<String>,
+}
+
+/// Labeled spotify tracks and the library they are matched against, in the format of
+/// the `--dump-tracks` files.
+#[derive(Deserialize)]
+pub struct Corpus {
+    #[serde(default)]
+    pub plex: Vec<TrackAlbumArtist>,
+    pub cases: Vec<Case>,
+}
+
+impl Corpus {
+    pub fn load(path: &str) -> Result<Self, anyhow::Error> {
+        let file = std::fs::File::open(path)?;
+        Ok(serde_json::from_reader(file)?)
+    }
+}
+
+pub struct Failure {
+    pub spotify: TrackAlbumArtist,
+    pub expected: Option<TrackAlbumArtist>,
+    pub found: Option<MatchResult>,
+}
+
+#[derive(Default)]
+pub struct Evaluation {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+    pub true_negatives: usize,
+    pub failures: Vec<Failure>,
+}
+
+impl Evaluation {
+    /// Share of the matches found that are right.
+    pub fn precision(&self) -> f64 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_positives,
+        )
+    }
+
+    /// Share of the tracks in the library that were found.
+    pub fn recall(&self) -> f64 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_negatives,
+        )
+    }
+}
+
+fn ratio(a: usize, b: usize) -> f64 {
+    match b {
+        0 => 1.0,
+        b => a as f64 / b as f64,
+    }
+}
+
+fn rating_key(track: &TrackAlbumArtist) -> Option<&String> {
+    match &track.metadata {
+        MetadataType::Plex(meta) => Some(&meta.rating_key),
+        MetadataType::Spotify(_) => None,
+    }
+}
+
+/// Runs the pipeline over every case, as one playlist for the one-to-one assignment.
+/// A match of the wrong track counts both as a false positive and a false negative.
+pub async fn evaluate(
+    cases: &[Case],
+    plex_tracks: &PlexIndex,
+    pipeline: &Pipeline,
+    config: &MatchConfig,
+    one_to_one: bool,
+) -> Evaluation {
+    let spotify_tracks: Vec<TrackAlbumArtist> = cases.iter().map(|c| c.spotify.clone()).collect();
+    let mut results = vec![];
+    for spotify_track in spotify_tracks.iter() {
+        results.push(pipeline.run(plex_tracks, spotify_track, config).await);
+    }
+    if one_to_one {
+        results = assignment::one_to_one(&spotify_tracks, &results, plex_tracks, config);
+    }
+
+    let mut evaluation = Evaluation::default();
+    for (case, found) in cases.iter().zip(results) {
+        let found_key = found.as_ref().and_then(|m| rating_key(&m.track));
+        match (case.expected.as_ref(), found_key) {
+            (Some(expected), Some(found)) if expected == found => {
+                evaluation.true_positives += 1;
+                continue;
+            }
+            (None, None) => {
+                evaluation.true_negatives += 1;
+                continue;
+            }
+            (Some(_), Some(_)) => {
+                evaluation.false_positives += 1;
+                evaluation.false_negatives += 1;
+            }
+            (None, Some(_)) => evaluation.false_positives += 1,
+            (Some(_), None) => evaluation.false_negatives += 1,
+        }
+        evaluation.failures.push(Failure {
+            spotify: case.spotify.clone(),
+            expected: case.expected.as_ref().and_then(|key| {
+                plex_tracks
+                    .tracks()
+                    .iter()
+                    .find(|t| rating_key(t) == Some(key))
+                    .cloned()
+            }),
+            found,
+        });
+    }
+    evaluation
+}
+
+impl fmt::Display for Evaluation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for failure in self.failures.iter() {
+            writeln!(
+                f,
+                "\"{}\": expected {}, found {}",
+                MatchExplanation::describe(&failure.spotify),
+                match &failure.expected {
+                    Some(track) => format!("\"{}\"", MatchExplanation::describe(track)),
+                    None => "no match".to_string(),
+                },
+                match &failure.found {
+                    Some(found) => found.explanation.to_string(),
+                    None => "no match".to_string(),
+                }
+            )?;
+        }
+        write!(
+            f,
+            "precision {:.3}, recall {:.3} ({} right, {} wrong, {} missed, {} rightly unmatched)",
+            self.precision(),
+            self.recall(),
+            self.true_positives,
+            self.false_positives,
+            self.false_negatives,
+            self.true_negatives
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plex::matcher::registry::{default_matchers, MatcherRegistry};
+
+    /// Fails when a matcher change makes the built in pipeline worse on the corpus,
+    /// raise the bounds when it gets better.
+    #[tokio::test]
+    async fn test_corpus() {
+        let corpus = Corpus::load(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/matcher_corpus.json"
+        ))
+        .unwrap();
+        let config = MatchConfig::default();
+        let pipeline = MatcherRegistry::default()
+            .build(&default_matchers(None))
+            .unwrap();
+        let plex_tracks = PlexIndex::new(corpus.plex, &config);
+        let evaluation = evaluate(&corpus.cases, &plex_tracks, &pipeline, &config, false).await;
+        println!("{}", evaluation);
+        assert!(evaluation.precision() >= 1.0, "{}", evaluation);
+        assert!(evaluation.recall() >= 1.0, "{}", evaluation);
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    io::{IsTerminal, Write},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use indicatif_log_bridge::LogWrapper;
+use log::info;
+
+/// How often progress is logged when there are no progress bars.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Set when progress bars are drawn, the logger pauses them to print its lines.
+static BARS: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Logs to stderr at info, or warn when quiet, unless `RUST_LOG` says otherwise. Json
+/// logs are one object per line. Progress bars are only drawn on a terminal and neither
+/// quiet nor json.
+pub fn init(quiet: bool, json: bool) -> Result<(), anyhow::Error> {
+    let level = if quiet { "warn" } else { "info" };
+    let mut builder =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(level));
+    if json {
+        builder.format(|buf, record| {
+            let line = serde_json::json!({
+                "timestamp": buf.timestamp_millis().to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{}", line)
+        });
+    }
+    let logger = builder.build();
+    let max_level = logger.filter();
+
+    if !quiet && !json && std::io::stderr().is_terminal() {
+        let bars = MultiProgress::new();
+        LogWrapper::new(bars.clone(), logger).try_init()?;
+        let _ = BARS.set(bars);
+    } else {
+        log::set_boxed_logger(Box::new(logger))?;
+    }
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// Progress of a long running step, a bar on a terminal and a log line every few
+/// seconds otherwise.
+pub struct Progress {
+    label: String,
+    total: u64,
+    done: u64,
+    message: String,
+    bar: Option<ProgressBar>,
+    logged: Instant,
+}
+
+impl Progress {
+    pub fn new(label: &str, total: u64) -> Self {
+        let bar = BARS.get().map(|bars| {
+            let bar = bars.add(ProgressBar::new(total));
+            bar.set_style(
+                ProgressStyle::with_template("{prefix} [{bar:40}] {pos}/{len} {msg}")
+                    .unwrap()
+                    .progress_chars("=> "),
+            );
+            bar.set_prefix(label.to_string());
+            bar
+        });
+        Self {
+            label: label.to_string(),
+            total,
+            done: 0,
+            message: String::new(),
+            bar,
+            logged: Instant::now(),
+        }
+    }
+
+    /// One more done, `message` is shown next to the count, e.g. what was found so far.
+    pub fn inc(&mut self, message: String) {
+        self.done += 1;
+        // kept for the line logged when it finishes.
+        self.message = message;
+        match &self.bar {
+            Some(bar) => {
+                bar.set_message(self.message.clone());
+                bar.inc(1);
+            }
+            None => {
+                if self.logged.elapsed() >= PROGRESS_INTERVAL {
+                    self.log();
+                    self.logged = Instant::now();
+                }
+            }
+        }
+    }
+
+    pub fn finish(self) {
+        match &self.bar {
+            Some(bar) => {
+                bar.finish();
+                if let Some(bars) = BARS.get() {
+                    bars.remove(bar);
+                }
+                info!("{}: {} {}", self.label, self.done, self.message);
+            }
+            None => self.log(),
+        }
+    }
+
+    fn log(&self) {
+        info!(
+            "{}: {} of {} {}",
+            self.label, self.done, self.total, self.message
+        );
+    }
+}
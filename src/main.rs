@@ -1,28 +1,57 @@
+mod artist_credit;
+mod config;
+mod evaluate;
+mod logging;
+mod musicbrainz;
+mod normalize;
 mod plex;
+mod report;
 mod spotify;
+mod state;
+mod sync;
 mod track_album_artist;
+mod track_version;
+mod transliterate;
+mod unmatched;
+mod watch;
 
-use std::collections::BTreeMap;
+use std::{collections::HashMap, time::Duration};
 
-use clap::{arg, command, Parser};
+use clap::{arg, command, Parser, Subcommand};
+use config::Config;
+use evaluate::{evaluate, Corpus};
+use log::info;
+use normalize::normalize;
 use plex::{
-    client::Plex,
-    get_plex_tracks,
+    get_library_version,
     matcher::{
-        character_replacement::MatchWithCharReplacements, forward_backward::MatchForwardBack,
-        levenshtein::LevenshteinDistance, remove_sections::RemoveSections, Matcher,
+        album::ReleaseType,
+        artist_alias::ArtistAliases,
+        duplicates::DuplicatePolicy,
+        index::PlexIndex,
+        pipeline::Pipeline,
+        quality::QualityPreference,
+        registry::{default_matchers, musicbrainz_matcher, MatcherRegistry},
+        MatchConfig,
     },
+    PlexLibrary,
 };
-use spotify::get_spotify_tracks;
-use track_album_artist::{MetadataType, TrackAlbumArtist};
+use report::Status;
+use spotify::{get_snapshot_id, get_spotify_tracks, spotify_client};
+use state::State;
+use sync::{download_unmatched, find_matches_and_update_playlist, PlexPlaylist, SyncOptions};
+use watch::Watcher;
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
-    #[arg(short = 'i', long)]
-    playlist_id: String,
-    #[arg(short = 'n', long)]
-    playlist_name: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(short = 'i', long, required = true)]
+    playlist_id: Option<String>,
+    #[arg(short = 'n', long, required = true)]
+    playlist_name: Option<String>,
 
     #[arg(short = 'c', long, required = false)]
     spotify_client_id: Option<String>,
@@ -36,14 +65,156 @@ struct Args {
 
     #[arg(short = 'd', long, required = false)]
     dump_tracks: bool,
+
+    /// Which release to prefer when the same track is on several albums.
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "studio,compilation,live"
+    )]
+    release_preference: Vec<ReleaseType>,
+
+    /// How to choose between copies of a track that match equally well, e.g. the same
+    /// album in flac and mp3.
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "release,lossless,bitrate"
+    )]
+    quality_preference: Vec<QualityPreference>,
+
+    /// Reject matches whose duration differs by more than this many seconds, 0 to disable.
+    #[arg(long, default_value_t = 10)]
+    duration_tolerance: u64,
+
+    /// Tab separated `isrc<TAB>recording mbid` export of a MusicBrainz dump.
+    #[arg(long, required = false)]
+    musicbrainz_dump: Option<String>,
+    /// Resolve ISRCs to MusicBrainz ids through the MusicBrainz web service.
+    #[arg(long, required = false, conflicts_with = "musicbrainz_dump")]
+    musicbrainz_api: bool,
+
+    /// Tab separated `native name<TAB>romanized name` table, e.g. for japanese names in kanji.
+    #[arg(long, required = false)]
+    transliteration_table: Option<String>,
+
+    /// Json file of `{"canonical artist": ["alias", ...]}` added to the built in aliases.
+    #[arg(long, required = false)]
+    artist_aliases: Option<String>,
+
+    /// Json config file, e.g. declaring the matchers to use.
+    #[arg(long, required = false)]
+    config: Option<String>,
+
+    /// Print which matcher matched each track, what it compared and how.
+    #[arg(long, required = false)]
+    explain: bool,
+
+    /// Use each plex track for at most one spotify track, the one it fits best.
+    #[arg(long, required = false)]
+    one_to_one: bool,
+
+    /// What to do when different spotify tracks are matched to the same plex track.
+    #[arg(long, value_enum, default_value = "allow")]
+    duplicates: DuplicatePolicy,
+
+    /// Write a json, csv and html report of the sync to this directory.
+    #[arg(long, required = false)]
+    report_dir: Option<String>,
+
+    /// Write the unmatched spotify tracks, grouped by album, to this directory as a csv
+    /// and as a list of spotify links for a downloader.
+    #[arg(long, required = false)]
+    unmatched_dir: Option<String>,
+
+    /// Only log warnings and errors, and draw no progress bars.
+    #[arg(short = 'q', long, required = false)]
+    quiet: bool,
+
+    /// Log one json object per line instead of text, and draw no progress bars.
+    #[arg(long, required = false)]
+    json_logs: bool,
+
+    /// Json file remembering what each playlist was last synced from.
+    #[arg(long, default_value = ".spotify_plex_state.json")]
+    state_file: String,
+
+    /// Shell command downloading the unmatched tracks into the plex library after the
+    /// sync, given a file of their spotify links as `$1`, e.g. `spotdl download $(cat "$1")`.
+    /// Plex is then asked to scan the library and the downloaded tracks are added.
+    #[arg(long, required = false)]
+    download_command: Option<String>,
+
+    /// Folder the download command saves to, so that plex only scans it.
+    #[arg(long, required = false, requires = "download_command")]
+    refresh_path: Option<String>,
+
+    /// Sync even when neither the playlist nor the plex library changed since the last sync.
+    #[arg(short = 'f', long, required = false)]
+    force: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the matchers over a labeled corpus and report their precision and recall.
+    Evaluate {
+        /// Json file of `{"plex": [...], "cases": [{"spotify": {...}, "expected": "rating key"}]}`.
+        corpus: String,
+        /// Match against this `--dump-tracks` library instead of the corpus' one.
+        #[arg(long)]
+        plex_tracks: Option<String>,
+    },
+    /// Keep running and sync the playlists of the config file on an interval.
+    Watch {
+        /// Seconds between syncs.
+        #[arg(long, default_value_t = 3600)]
+        interval: u64,
+        /// Listen for plex webhooks on this address, e.g. `0.0.0.0:8585`, to match the
+        /// tracks added to the library right away.
+        #[arg(long)]
+        webhook: Option<String>,
+        /// Secret the webhook url of plex carries as its `token` query parameter, read
+        /// from WEBHOOK_TOKEN when not given. Required with `--webhook`.
+        #[arg(long)]
+        webhook_token: Option<String>,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
-    // You can use any logger for debugging.
-    env_logger::init();
-
     let args = Args::parse();
+    logging::init(args.quiet, args.json_logs)?;
+    let settings = match &args.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    let pipeline = build_pipeline(&args, &settings)?;
+    let config = match_config(&args)?;
+
+    if let Some(Command::Evaluate {
+        corpus,
+        plex_tracks,
+    }) = &args.command
+    {
+        let corpus = Corpus::load(corpus)?;
+        let plex_tracks = match plex_tracks {
+            Some(path) => serde_json::from_reader(std::fs::File::open(path)?)?,
+            None => corpus.plex,
+        };
+        let plex_tracks = PlexIndex::new(plex_tracks, &config);
+        let evaluation = evaluate(
+            &corpus.cases,
+            &plex_tracks,
+            &pipeline,
+            &config,
+            args.one_to_one,
+        )
+        .await;
+        println!("{}", evaluation);
+        return Ok(());
+    }
 
     let spotify_client_id = match args.spotify_client_id {
         Some(client_id) => client_id,
@@ -66,9 +237,69 @@ async fn main() -> Result<(), anyhow::Error> {
     };
 
     let plex = plex::client::Plex::new(plex_url, plex_token);
-    let spotify_tracks =
-        get_spotify_tracks(spotify_client_id, spotify_client_secret, args.playlist_id).await?;
-    let plex_tracks = get_plex_tracks(&plex).await?;
+    let spotify = spotify_client(&spotify_client_id, &spotify_client_secret)?;
+    let options = SyncOptions {
+        explain: args.explain,
+        one_to_one: args.one_to_one,
+        duplicates: args.duplicates,
+        report_dir: args.report_dir,
+        unmatched_dir: args.unmatched_dir,
+        download_command: args.download_command,
+        refresh_path: args.refresh_path,
+    };
+
+    if let Some(Command::Watch {
+        interval,
+        webhook,
+        webhook_token,
+    }) = args.command
+    {
+        if settings.playlists.is_empty() {
+            anyhow::bail!("no playlists to watch, list them in the --config file");
+        }
+        let webhook_token = webhook_token
+            .or_else(|| std::env::var("WEBHOOK_TOKEN").ok())
+            .unwrap_or_default();
+        if webhook.is_some() && webhook_token.is_empty() {
+            anyhow::bail!("--webhook needs a --webhook-token or WEBHOOK_TOKEN");
+        }
+        let watcher = Watcher {
+            plex: &plex,
+            spotify: &spotify,
+            config: &config,
+            pipeline: &pipeline,
+            options: &options,
+            state_file: &args.state_file,
+            force: args.force,
+            webhook,
+            webhook_token,
+        };
+        return watcher
+            .run(&settings.playlists, Duration::from_secs(interval))
+            .await;
+    }
+
+    // only optional for the subcommands.
+    let playlist_id = args.playlist_id.expect("playlist id not set");
+    let playlist_name = args.playlist_name.expect("playlist name not set");
+
+    let mut state = State::load(&args.state_file)?;
+    let snapshot_id = get_snapshot_id(&spotify, &playlist_id)?;
+    let library_version = get_library_version(&plex).await?;
+    if !args.force
+        && !args.dump_tracks
+        && state.is_synced(&playlist_id, &snapshot_id, library_version)
+    {
+        info!(
+            "{} and the library didn't change since the last sync, use --force to sync anyway",
+            playlist_name
+        );
+        return Ok(());
+    }
+
+    let spotify_tracks = get_spotify_tracks(&spotify, &playlist_id).await?;
+    let mut library = PlexLibrary::scan(&plex).await?;
+    let plex_tracks = library.tracks();
 
     if args.dump_tracks {
         let file = std::fs::File::create("plex_tracks.json")?;
@@ -78,80 +309,95 @@ async fn main() -> Result<(), anyhow::Error> {
         return Ok(());
     }
 
-    // try to find a single match?
-    find_matches_and_update_playlist(&plex, &spotify_tracks, &plex_tracks, &args.playlist_name)
+    // built once, every matcher looks up its candidates in it.
+    let plex_tracks = PlexIndex::new(plex_tracks, &config);
+
+    // synced before, its tracks are replaced instead of creating another playlist.
+    let mut plex_playlist = PlexPlaylist {
+        name: playlist_name,
+        id: state.plex_playlist_id(&playlist_id),
+    };
+    let report = find_matches_and_update_playlist(
+        &plex,
+        &spotify_tracks,
+        &plex_tracks,
+        &mut plex_playlist,
+        &config,
+        &pipeline,
+        &options,
+    )
+    .await?;
+    let mut library_version = library_version;
+    if options.download_command.is_some() {
+        let unmatched = report
+            .entries
+            .into_iter()
+            .filter(|e| e.status == Status::Unmatched)
+            .map(|e| e.spotify)
+            .collect();
+        download_unmatched(
+            &plex,
+            &mut library,
+            unmatched,
+            &mut plex_playlist,
+            &config,
+            &pipeline,
+            &options,
+        )
         .await?;
+        library_version = get_library_version(&plex).await?;
+    }
+    state.synced(
+        &playlist_id,
+        &snapshot_id,
+        library_version,
+        &plex_playlist.id,
+    );
+    state.save(&args.state_file)?;
 
     Ok(())
 }
 
-async fn find_matches_and_update_playlist(
-    plex: &Plex,
-    spotify_tracks: &Vec<TrackAlbumArtist>,
-    plex_tracks: &Vec<TrackAlbumArtist>,
-    playlist_name: &String,
-) -> Result<(), anyhow::Error> {
-    let mut playlist_id = String::default();
-    let mut dupe_list: BTreeMap<String, (TrackAlbumArtist, TrackAlbumArtist)> = BTreeMap::new();
-    'outer: for spotify_track in spotify_tracks.iter() {
-        let list_of_fns: Vec<Box<dyn Matcher>> = vec![
-            Box::new(MatchForwardBack {}),
-            Box::new(LevenshteinDistance {}),
-            Box::new(MatchWithCharReplacements {}),
-            Box::new(RemoveSections {}),
-        ];
-
-        for fun in list_of_fns {
-            let result = fun
-                .match_fn(
-                    &mut playlist_id,
-                    &plex,
-                    &plex_tracks,
-                    &spotify_track,
-                    &playlist_name,
-                )
-                .await;
-            if result.is_ok() {
-                let track_result = result.unwrap();
-                if let MetadataType::Plex(meta) = &track_result.metadata {
-                    if !dupe_list.contains_key(&meta.rating_key) {
-                        dupe_list.insert(
-                            meta.rating_key.clone(),
-                            (spotify_track.clone(), track_result.clone()),
-                        );
-                    } else {
-                        let matched = dupe_list.get(&meta.rating_key).unwrap();
-                        println!(
-                            "Found a duplicate result: ([{:?} - {}] already matched [{:?} - {}]) => new match: {:?} - {}",
-                            matched.0.artist, matched.0.track, matched.1.artist, matched.1.track, track_result.artist, track_result.track
-                        );
-                    }
-                }
-                continue 'outer;
-            }
-        }
-        println!("===================================");
-        println!(
-            "No match found for: {:?} - {}",
-            spotify_track.artist, spotify_track.track
-        );
-        let mut immediate_dupe_list = BTreeMap::new();
-        for plex_track in plex_tracks.iter() {
-            if plex_track.artist == spotify_track.artist || plex_track.album == spotify_track.album
-            {
-                let key = format!("{:?}{}", plex_track.artist, plex_track.album);
-                if immediate_dupe_list.contains_key(&key) {
-                    continue;
-                }
-                immediate_dupe_list.insert(key, plex_track);
-                println!(
-                    "Match from the artist/album: {:?} - {}",
-                    plex_track.artist, plex_track.track
-                );
-            }
-        }
-        println!("===================================");
-    }
+fn build_pipeline(args: &Args, settings: &Config) -> Result<Pipeline, anyhow::Error> {
+    let matchers = settings.matchers.clone().unwrap_or_else(|| {
+        default_matchers(musicbrainz_matcher(
+            args.musicbrainz_dump.clone(),
+            args.musicbrainz_api,
+        ))
+    });
+    let pipeline = MatcherRegistry::default().build(&matchers)?;
+    info!(
+        "Matching with: {}",
+        pipeline.names().collect::<Vec<&str>>().join(", ")
+    );
 
-    Ok(())
+    Ok(pipeline)
+}
+
+fn match_config(args: &Args) -> Result<MatchConfig, anyhow::Error> {
+    Ok(MatchConfig {
+        release_preference: args.release_preference.clone(),
+        duration_tolerance_ms: match args.duration_tolerance {
+            0 => None,
+            seconds => Some(seconds * 1000),
+        },
+        transliterations: match &args.transliteration_table {
+            Some(path) => load_transliteration_table(path)?,
+            None => HashMap::new(),
+        },
+        artist_aliases: match &args.artist_aliases {
+            Some(path) => ArtistAliases::load(path)?,
+            None => ArtistAliases::default(),
+        },
+        quality_preference: args.quality_preference.clone(),
+    })
+}
+
+fn load_transliteration_table(path: &str) -> Result<HashMap<String, String>, anyhow::Error> {
+    let table = std::fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(native, romanized)| (normalize(native), normalize(romanized)))
+        .collect();
+    Ok(table)
 }
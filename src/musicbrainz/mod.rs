@@ -0,0 +1,134 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    sync::Mutex,
+    time::Duration,
+};
+
+use serde_derive::Deserialize;
+use tokio::time::Instant;
+
+/// Resolves ISRCs to MusicBrainz recording ids.
+#[async_trait::async_trait]
+pub trait MusicBrainzLookup: Send + Sync {
+    async fn recordings_for_isrc(&self, isrc: &str) -> Result<Vec<String>, anyhow::Error>;
+}
+
+/// ISRC to recording id table exported from a MusicBrainz database dump, one
+/// `isrc<TAB>recording mbid` pair per line.
+pub struct MusicBrainzDump {
+    recordings: HashMap<String, Vec<String>>,
+}
+
+impl MusicBrainzDump {
+    pub fn load(path: &str) -> Result<Self, anyhow::Error> {
+        let file = std::fs::File::open(path)?;
+        let mut recordings: HashMap<String, Vec<String>> = HashMap::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let Some((isrc, mbid)) = line.split_once('\t') else {
+                continue;
+            };
+            recordings
+                .entry(isrc.trim().to_uppercase())
+                .or_default()
+                .push(mbid.trim().to_lowercase());
+        }
+        Ok(Self { recordings })
+    }
+}
+
+#[async_trait::async_trait]
+impl MusicBrainzLookup for MusicBrainzDump {
+    async fn recordings_for_isrc(&self, isrc: &str) -> Result<Vec<String>, anyhow::Error> {
+        Ok(self.recordings.get(isrc).cloned().unwrap_or_default())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct IsrcResponse {
+    recordings: Vec<Recording>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Recording {
+    id: String,
+}
+
+/// Queries the MusicBrainz web service, limited to one request per second as required
+/// by their usage policy. Answers are kept for the whole run, so a track that stays
+/// unmatched is only looked up once.
+pub struct MusicBrainzApi {
+    client: reqwest::Client,
+    /// When the last request was allowed to start.
+    last_request: Mutex<Option<Instant>>,
+    cache: Mutex<HashMap<String, Vec<String>>>,
+}
+
+impl MusicBrainzApi {
+    pub fn new() -> Result<Self, anyhow::Error> {
+        let client = reqwest::Client::builder()
+            .user_agent(concat!(
+                env!("CARGO_PKG_NAME"),
+                "/",
+                env!("CARGO_PKG_VERSION"),
+                " ( https://github.com/zacbre/spotify-plex-dl )"
+            ))
+            .build()?;
+        Ok(Self {
+            client,
+            last_request: Mutex::new(None),
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Takes the next free slot, a second after the one before, and waits for it. The
+    /// lock is only held while taking the slot, not during the request.
+    async fn wait_turn(&self) {
+        let slot = {
+            let mut last_request = self.last_request.lock().unwrap();
+            let slot = match *last_request {
+                Some(last) => (last + Duration::from_secs(1)).max(Instant::now()),
+                None => Instant::now(),
+            };
+            *last_request = Some(slot);
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+#[async_trait::async_trait]
+impl MusicBrainzLookup for MusicBrainzApi {
+    async fn recordings_for_isrc(&self, isrc: &str) -> Result<Vec<String>, anyhow::Error> {
+        if let Some(recordings) = self.cache.lock().unwrap().get(isrc) {
+            return Ok(recordings.clone());
+        }
+        self.wait_turn().await;
+
+        let response = self
+            .client
+            .get(format!(
+                "https://musicbrainz.org/ws/2/isrc/{}?fmt=json",
+                isrc
+            ))
+            .send()
+            .await?;
+        // unknown isrcs are a 404.
+        let recordings = if response.status() == reqwest::StatusCode::NOT_FOUND {
+            vec![]
+        } else {
+            let response: IsrcResponse = response.error_for_status()?.json().await?;
+            response
+                .recordings
+                .into_iter()
+                .map(|r| r.id.to_lowercase())
+                .collect()
+        };
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(isrc.to_string(), recordings.clone());
+        Ok(recordings)
+    }
+}
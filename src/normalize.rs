@@ -0,0 +1,125 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Letters that don't decompose into a base letter and a diacritic.
+const FOLDED_LETTERS: [(char, &str); 9] = [
+    ('ø', "o"),
+    ('æ', "ae"),
+    ('œ', "oe"),
+    ('ß', "ss"),
+    ('ł', "l"),
+    ('đ', "d"),
+    ('ð', "d"),
+    ('þ', "th"),
+    ('ı', "i"),
+];
+
+fn is_latin_diacritic(c: char) -> bool {
+    // only the combining diacritical marks block, so that e.g. the kana voicing marks survive.
+    ('\u{0300}'..='\u{036f}').contains(&c)
+}
+
+fn unify_punctuation(c: char) -> char {
+    match c {
+        '‘' | '’' | '‚' | '‛' | '′' | '`' | '´' => '\'',
+        '“' | '”' | '„' | '‟' | '″' => '"',
+        '‐' | '‑' | '‒' | '–' | '—' | '―' | '−' => '-',
+        _ => c,
+    }
+}
+
+/// Normalizes titles and artists from both spotify and plex so they can be compared:
+/// NFKC, lowercase, no diacritics, plain quotes and dashes and single spaces.
+pub fn normalize(s: &str) -> String {
+    let folded: String = s
+        .nfkc()
+        .flat_map(|c| c.to_lowercase())
+        .nfd()
+        .filter(|c| !is_latin_diacritic(*c))
+        .nfc()
+        .map(unify_punctuation)
+        .fold(String::new(), |mut acc, c| {
+            match FOLDED_LETTERS.iter().find(|(from, _)| *from == c) {
+                Some((_, to)) => acc.push_str(to),
+                None => acc.push(c),
+            }
+            acc
+        });
+
+    folded.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Stricter form of [`normalize`] for comparing strings that differ in punctuation,
+/// e.g. "simon & garfunkel" and "simon and garfunkel" or "ac/dc" and "acdc".
+pub fn comparison_key(s: &str) -> String {
+    let stripped: String = normalize(s)
+        .replace('&', " and ")
+        .chars()
+        .filter_map(|c| match c {
+            '-' | '_' => Some(' '),
+            c if c.is_alphanumeric() || c.is_whitespace() => Some(c),
+            _ => None,
+        })
+        .collect();
+
+    stripped.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let cases = [
+            ("Beyoncé", "beyonce"),
+            ("MÖTLEY CRÜE", "motley crue"),
+            ("Sigur Rós", "sigur ros"),
+            ("Røyksopp", "royksopp"),
+            ("Mötörhead", "motorhead"),
+            ("Straße", "strasse"),
+            ("Don’t Stop Me Now", "don't stop me now"),
+            ("“Heroes”", "\"heroes\""),
+            ("Song – Live", "song - live"),
+            ("Song — Remastered", "song - remastered"),
+            ("  Too   many\tspaces  ", "too many spaces"),
+            ("Non\u{a0}breaking", "non breaking"),
+            ("ｆｕｌｌｗｉｄｔｈ", "fullwidth"),
+            ("ﬁne", "fine"),
+            ("Ⅻ", "xii"),
+            ("ガンダム", "ガンダム"),
+            ("坂本龍一", "坂本龍一"),
+            ("방탄소년단", "방탄소년단"),
+            ("Кино", "кино"),
+            ("AC/DC", "ac/dc"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(normalize(input), expected, "normalize({:?})", input);
+        }
+    }
+
+    #[test]
+    fn test_comparison_key() {
+        let cases = [
+            ("Simon & Garfunkel", "simon and garfunkel"),
+            ("Simon and Garfunkel", "simon and garfunkel"),
+            ("AC/DC", "acdc"),
+            ("Guns N’ Roses", "guns n roses"),
+            ("Mr. Brightside", "mr brightside"),
+            ("Jay-Z", "jay z"),
+            ("Song (Remastered)", "song remastered"),
+            ("P!nk", "pnk"),
+            ("Beyoncé", "beyonce"),
+            ("Hello, World!", "hello world"),
+            ("What’s Up?", "whats up"),
+            ("a  -  b", "a b"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                comparison_key(input),
+                expected,
+                "comparison_key({:?})",
+                input
+            );
+        }
+    }
+}
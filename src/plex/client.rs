@@ -1,8 +1,9 @@
 use reqwest::Response;
 
 use super::models::{
-    extras::ExtrasMediaContainer, metadata::MetadataMediaContainer,
-    providers::ProviderMediaContainer, MediaContainerWrapper,
+    activities::ActivityMediaContainer, extras::ExtrasMediaContainer,
+    metadata::MetadataMediaContainer, providers::ProviderMediaContainer,
+    sections::SectionMediaContainer, MediaContainerWrapper,
 };
 
 pub struct Plex {
@@ -51,6 +52,18 @@ impl Plex {
         Ok(response)
     }
 
+    pub async fn delete(&self, url: &str) -> Result<Response, anyhow::Error> {
+        let client = reqwest::Client::new();
+        let response = client
+            .delete(format!("{}/{}", self.base_url, url))
+            .header("X-Plex-Token", &self.token)
+            .header("Accept", "application/json")
+            .send()
+            .await?;
+
+        Ok(response)
+    }
+
     pub async fn get_providers(
         &self,
     ) -> Result<MediaContainerWrapper<ProviderMediaContainer>, anyhow::Error> {
@@ -59,6 +72,43 @@ impl Plex {
         Ok(providers)
     }
 
+    pub async fn get_sections(
+        &self,
+    ) -> Result<MediaContainerWrapper<SectionMediaContainer>, anyhow::Error> {
+        let sections: MediaContainerWrapper<SectionMediaContainer> =
+            self.get("library/sections").await?.json().await?;
+        Ok(sections)
+    }
+
+    /// Starts scanning the section for new files, only the folder `path` when given.
+    pub async fn refresh_section(
+        &self,
+        section: &str,
+        path: Option<&str>,
+    ) -> Result<(), anyhow::Error> {
+        let client = reqwest::Client::new();
+        let mut request = client
+            .get(format!(
+                "{}/library/sections/{}/refresh",
+                self.base_url, section
+            ))
+            .header("X-Plex-Token", &self.token)
+            .header("Accept", "application/json");
+        if let Some(path) = path {
+            request = request.query(&[("path", path)]);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    pub async fn get_activities(
+        &self,
+    ) -> Result<MediaContainerWrapper<ActivityMediaContainer>, anyhow::Error> {
+        let activities: MediaContainerWrapper<ActivityMediaContainer> =
+            self.get("activities").await?.json().await?;
+        Ok(activities)
+    }
+
     pub async fn get_artists(
         &self,
         section: String,
@@ -77,7 +127,7 @@ impl Plex {
     ) -> Result<MediaContainerWrapper<MetadataMediaContainer>, anyhow::Error> {
         //let response = self.get(format!("library/sections/{}/all?format!=EP,Single,Compilation,Live,Soundtrack&artist.id={}&includeMetadata=1&type={}&X-Plex-Container-Start=0&X-Plex-Container-Size=1000", section, artist, type_id).as_str()).await?;
         //println!("{:?}", response.text().await?);
-        let response = self.get(format!("library/sections/{}/all?format!=EP,Single,Compilation,Live,Soundtrack&artist.id={}&includeMetadata=1&type={}&X-Plex-Container-Start=0&X-Plex-Container-Size=1000", section, artist, type_id).as_str()).await?;
+        let response = self.get(format!("library/sections/{}/all?format!=EP,Single,Compilation,Live,Soundtrack&artist.id={}&includeMetadata=1&includeGuids=1&type={}&X-Plex-Container-Start=0&X-Plex-Container-Size=1000", section, artist, type_id).as_str()).await?;
         let extra_items: MediaContainerWrapper<MetadataMediaContainer> = response.json().await?;
         Ok(extra_items)
     }
@@ -111,7 +161,7 @@ impl Plex {
         key: &str,
     ) -> Result<MediaContainerWrapper<MetadataMediaContainer>, anyhow::Error> {
         let metadata: MediaContainerWrapper<MetadataMediaContainer> = self
-            .get(format!("library/metadata/{}/children", key).as_str())
+            .get(format!("library/metadata/{}/children?includeGuids=1", key).as_str())
             .await?
             .json()
             .await?;
@@ -161,4 +211,12 @@ impl Plex {
 
         Ok(response)
     }
+
+    /// Removes every item, the playlist itself is kept.
+    pub async fn clear_playlist(&self, playlist_key: &str) -> Result<(), anyhow::Error> {
+        self.delete(format!("playlists/{}/items", playlist_key).as_str())
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
 }
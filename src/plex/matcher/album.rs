@@ -0,0 +1,116 @@
+use clap::ValueEnum;
+use levenshtein::levenshtein;
+
+use super::MatchConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReleaseType {
+    Studio,
+    Compilation,
+    Live,
+}
+
+const LIVE_MARKERS: [&str; 4] = ["live", "unplugged", "in concert", "bbc sessions"];
+const COMPILATION_MARKERS: [&str; 10] = [
+    "greatest hits",
+    "best of",
+    "the very best",
+    "anthology",
+    "collection",
+    "essential",
+    "singles",
+    "hits",
+    "now that's what i call",
+    "compilation",
+];
+
+/// Guess the kind of release from an album title, e.g. "live at wembley" => Live.
+pub fn release_type(album: &str) -> ReleaseType {
+    let words = album
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ");
+    let contains = |marker: &str| {
+        words == marker
+            || words.starts_with(&format!("{} ", marker))
+            || words.ends_with(&format!(" {}", marker))
+            || words.contains(&format!(" {} ", marker))
+    };
+
+    if LIVE_MARKERS.iter().any(|m| contains(m)) {
+        ReleaseType::Live
+    } else if COMPILATION_MARKERS.iter().any(|m| contains(m)) {
+        ReleaseType::Compilation
+    } else {
+        ReleaseType::Studio
+    }
+}
+
+/// Similarity of two album titles between 0.0 (nothing in common) and 1.0 (identical).
+pub fn album_similarity(spotify_album: &str, plex_album: &str) -> f64 {
+    let longest = spotify_album
+        .chars()
+        .count()
+        .max(plex_album.chars().count());
+    if longest == 0 {
+        return 0.0;
+    }
+    1.0 - levenshtein(spotify_album, plex_album) as f64 / longest as f64
+}
+
+pub fn release_rank(release: ReleaseType, config: &MatchConfig) -> usize {
+    config
+        .release_preference
+        .iter()
+        .position(|r| *r == release)
+        .unwrap_or(config.release_preference.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_type() {
+        let cases = [
+            ("discovery", ReleaseType::Studio),
+            ("live at wembley", ReleaseType::Live),
+            ("mtv unplugged in new york", ReleaseType::Live),
+            ("alive 2007", ReleaseType::Studio),
+            ("the very best of fleetwood mac", ReleaseType::Compilation),
+            ("greatest hits", ReleaseType::Compilation),
+            ("1 (remastered)", ReleaseType::Studio),
+            ("whitney hits", ReleaseType::Compilation),
+            ("hitsville", ReleaseType::Studio),
+        ];
+        for (album, expected) in cases {
+            assert_eq!(release_type(album), expected, "{}", album);
+        }
+    }
+
+    #[test]
+    fn test_album_similarity() {
+        assert_eq!(album_similarity("discovery", "discovery"), 1.0);
+        assert_eq!(album_similarity("", ""), 0.0);
+        assert_eq!(album_similarity("abc", "xyz"), 0.0);
+        // one edit in eight characters.
+        assert_eq!(album_similarity("homework", "homewerk"), 0.875);
+        assert!(
+            album_similarity("discovery", "discovery (deluxe)")
+                > album_similarity("discovery", "alive 1997")
+        );
+    }
+
+    #[test]
+    fn test_release_rank() {
+        let config = MatchConfig {
+            release_preference: vec![ReleaseType::Live, ReleaseType::Studio],
+            ..Default::default()
+        };
+        assert_eq!(release_rank(ReleaseType::Live, &config), 0);
+        assert_eq!(release_rank(ReleaseType::Studio, &config), 1);
+        // not in the preference at all, after every listed one.
+        assert_eq!(release_rank(ReleaseType::Compilation, &config), 2);
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use crate::normalize::{comparison_key, normalize};
+
+/// Artists known under several names, the first name of each group is the canonical one.
+const DEFAULT_ALIASES: [&[&str]; 6] = [
+    &["pink", "p!nk"],
+    &["kanye west", "ye"],
+    &["ke$ha", "kesha"],
+    &["prince", "the artist formerly known as prince"],
+    &["puff daddy", "p. diddy", "diddy", "sean combs"],
+    &["snoop dogg", "snoop doggy dogg", "snoop lion"],
+];
+
+/// "the beatles" and "beatles, the" are both "beatles".
+fn without_article(artist: &str) -> &str {
+    let artist = artist.strip_suffix(", the").unwrap_or(artist);
+    artist.strip_prefix("the ").unwrap_or(artist)
+}
+
+fn alias_key(artist: &str) -> String {
+    comparison_key(without_article(&normalize(artist)))
+}
+
+pub struct ArtistAliases {
+    canonical: HashMap<String, String>,
+}
+
+impl ArtistAliases {
+    pub fn new<I, G, S>(groups: I) -> Self
+    where
+        I: IntoIterator<Item = G>,
+        G: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut aliases = Self {
+            canonical: HashMap::new(),
+        };
+        aliases.extend(groups);
+        aliases
+    }
+
+    fn extend<I, G, S>(&mut self, groups: I)
+    where
+        I: IntoIterator<Item = G>,
+        G: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for group in groups {
+            let keys: Vec<String> = group.into_iter().map(|a| alias_key(a.as_ref())).collect();
+            let Some(canonical) = keys.first() else {
+                continue;
+            };
+            for key in keys.iter() {
+                self.canonical.insert(key.clone(), canonical.clone());
+            }
+        }
+    }
+
+    /// Adds the aliases from a json file of `{"canonical name": ["alias", ...]}`, on top
+    /// of the built in ones.
+    pub fn load(path: &str) -> Result<Self, anyhow::Error> {
+        let file = std::fs::File::open(path)?;
+        let table: HashMap<String, Vec<String>> = serde_json::from_reader(file)?;
+        let mut aliases = Self::default();
+        aliases.extend(table.into_iter().map(|(canonical, others)| {
+            std::iter::once(canonical)
+                .chain(others)
+                .collect::<Vec<String>>()
+        }));
+        Ok(aliases)
+    }
+
+    /// The name to compare an artist by: the canonical name of its alias group, otherwise
+    /// the artist itself, both as a comparison key without a leading or trailing "the".
+    pub fn canonical(&self, artist: &str) -> String {
+        let key = alias_key(artist);
+        self.canonical.get(&key).cloned().unwrap_or(key)
+    }
+}
+
+impl Default for ArtistAliases {
+    fn default() -> Self {
+        Self::new(DEFAULT_ALIASES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical() {
+        let aliases = ArtistAliases::default();
+        let same = [
+            ("pink", "p!nk"),
+            ("ye", "kanye west"),
+            ("the weeknd", "weeknd"),
+            ("beatles, the", "the beatles"),
+            ("simon & garfunkel", "simon and garfunkel"),
+        ];
+        for (a, b) in same {
+            assert_eq!(aliases.canonical(a), aliases.canonical(b), "{} = {}", a, b);
+        }
+        assert_ne!(aliases.canonical("the the"), aliases.canonical("pink"));
+
+        // names with and without an alias come out in the same form.
+        let cases = [
+            ("P!nk", "pink"),
+            ("The Weeknd", "weeknd"),
+            ("AC/DC", "acdc"),
+            ("Simon & Garfunkel", "simon and garfunkel"),
+            ("Beatles, The", "beatles"),
+        ];
+        for (artist, expected) in cases {
+            assert_eq!(aliases.canonical(artist), expected, "{}", artist);
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+
+use log::info;
+
+use crate::{normalize::comparison_key, track_album_artist::TrackAlbumArtist};
+
+use super::{
+    duration::within_tolerance, explain::MatchExplanation, index::PlexIndex, ranking,
+    token_similarity::token_similarity, version::compatible, MatchConfig, MatchResult,
+};
+
+/// Minimum similarity of the artist and the title for a plex track to be an alternative
+/// to the one the pipeline picked.
+const ALTERNATIVE_THRESHOLD: f64 = 0.85;
+/// The pipeline's pick is kept unless giving it up makes the playlist better overall.
+const PICKED_BONUS: f64 = 2.0;
+/// Names count as much as the release.
+const NAME_WEIGHT: f64 = 6.0;
+/// Cost of the pairs that can't be assigned, far above any weight.
+const FORBIDDEN: f64 = 1e9;
+
+/// Assignment of rows to columns maximizing the sum of the weights, each column used
+/// at most once. `None` is a pair that can't be assigned, rows without any column
+/// available are left unassigned.
+pub fn assign(weights: &[Vec<Option<f64>>]) -> Vec<Option<usize>> {
+    let rows = weights.len();
+    let columns = weights.iter().map(|w| w.len()).max().unwrap_or(0);
+    // every row also gets a column of its own for leaving it unassigned, which makes
+    // the matrix at least as wide as it is high.
+    let width = columns + rows;
+    let cost = |row: usize, column: usize| -> f64 {
+        if column >= columns {
+            return if column - columns == row {
+                0.0
+            } else {
+                FORBIDDEN
+            };
+        }
+        match weights[row].get(column).copied().flatten() {
+            Some(weight) => -weight,
+            None => FORBIDDEN,
+        }
+    };
+
+    // hungarian algorithm with potentials, rows and columns are 1 based, column 0 is
+    // the row being added.
+    let mut u = vec![0.0; rows + 1];
+    let mut v = vec![0.0; width + 1];
+    let mut row_of = vec![0; width + 1];
+    let mut way = vec![0; width + 1];
+    for row in 1..=rows {
+        row_of[0] = row;
+        let mut column = 0;
+        let mut min = vec![f64::INFINITY; width + 1];
+        let mut used = vec![false; width + 1];
+        loop {
+            used[column] = true;
+            let current = row_of[column];
+            let mut delta = f64::INFINITY;
+            let mut next = 0;
+            for j in 1..=width {
+                if used[j] {
+                    continue;
+                }
+                let reduced = cost(current - 1, j - 1) - u[current] - v[j];
+                if reduced < min[j] {
+                    min[j] = reduced;
+                    way[j] = column;
+                }
+                if min[j] < delta {
+                    delta = min[j];
+                    next = j;
+                }
+            }
+            for j in 0..=width {
+                if used[j] {
+                    u[row_of[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min[j] -= delta;
+                }
+            }
+            column = next;
+            if row_of[column] == 0 {
+                break;
+            }
+        }
+        loop {
+            let previous = way[column];
+            row_of[column] = row_of[previous];
+            column = previous;
+            if column == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut assignment = vec![None; rows];
+    for (column, row) in row_of.iter().enumerate().skip(1) {
+        if *row != 0 && column <= columns && cost(row - 1, column - 1) < FORBIDDEN {
+            assignment[row - 1] = Some(column - 1);
+        }
+    }
+    assignment
+}
+
+/// Assigns the rows sharing columns together, there are few of them in a playlist.
+fn assign_components(edges: &[HashMap<usize, f64>], columns: usize) -> Vec<Option<usize>> {
+    // union find over the rows, joined through their columns.
+    let mut parent: Vec<usize> = (0..edges.len()).collect();
+    fn root(parent: &mut [usize], mut row: usize) -> usize {
+        while parent[row] != row {
+            parent[row] = parent[parent[row]];
+            row = parent[row];
+        }
+        row
+    }
+    let mut first_row: Vec<Option<usize>> = vec![None; columns];
+    for (row, columns) in edges.iter().enumerate() {
+        for column in columns.keys() {
+            match first_row[*column] {
+                Some(other) => {
+                    let (a, b) = (root(&mut parent, row), root(&mut parent, other));
+                    parent[a] = b;
+                }
+                None => first_row[*column] = Some(row),
+            }
+        }
+    }
+    let mut components: HashMap<usize, Vec<usize>> = HashMap::new();
+    for row in 0..edges.len() {
+        components
+            .entry(root(&mut parent, row))
+            .or_default()
+            .push(row);
+    }
+
+    let mut assignment = vec![None; edges.len()];
+    for rows in components.values() {
+        let mut local_columns: Vec<usize> = rows
+            .iter()
+            .flat_map(|row| edges[*row].keys().copied())
+            .collect();
+        local_columns.sort_unstable();
+        local_columns.dedup();
+        let weights: Vec<Vec<Option<f64>>> = rows
+            .iter()
+            .map(|row| {
+                local_columns
+                    .iter()
+                    .map(|column| edges[*row].get(column).copied())
+                    .collect()
+            })
+            .collect();
+        for (row, column) in rows.iter().zip(assign(&weights)) {
+            assignment[*row] = column.map(|c| local_columns[c]);
+        }
+    }
+    assignment
+}
+
+fn names_similarity(
+    spotify_track: &TrackAlbumArtist,
+    plex_track: &TrackAlbumArtist,
+    config: &MatchConfig,
+) -> (f64, f64) {
+    let key = |a: &String| config.artist_aliases.canonical(a);
+    let artist = plex_track
+        .credited_artists()
+        .map(key)
+        .flat_map(|p| {
+            spotify_track
+                .credited_artists()
+                .map(move |s| token_similarity(&key(s), &p))
+        })
+        .fold(0.0, f64::max);
+    let title = token_similarity(
+        &comparison_key(&spotify_track.track),
+        &comparison_key(&plex_track.track),
+    );
+    (artist, title)
+}
+
+fn weight(
+    spotify_track: &TrackAlbumArtist,
+    plex_track: &TrackAlbumArtist,
+    config: &MatchConfig,
+) -> f64 {
+    let (artist, title) = names_similarity(spotify_track, plex_track, config);
+    NAME_WEIGHT * (artist + title) / 2.0 + ranking::score(plex_track, spotify_track, config)
+}
+
+/// Plex tracks the spotify track could as well have been matched to.
+fn alternatives<'a>(
+    spotify_track: &TrackAlbumArtist,
+    plex_tracks: &'a PlexIndex,
+    config: &MatchConfig,
+) -> Vec<&'a TrackAlbumArtist> {
+    plex_tracks
+        .candidates(spotify_track, config)
+        .into_iter()
+        .filter(|p| within_tolerance(spotify_track, p, config) && compatible(spotify_track, p))
+        .filter(|p| {
+            let (artist, title) = names_similarity(spotify_track, p, config);
+            artist >= ALTERNATIVE_THRESHOLD && title >= ALTERNATIVE_THRESHOLD
+        })
+        .collect()
+}
+
+/// Reassigns the matches so that no plex track is used for two different spotify
+/// tracks, maximizing the overall quality of the matches. Spotify tracks that are in
+/// the playlist several times keep sharing their plex track. Tracks the pipeline
+/// didn't match are left alone, tracks whose match went to a better fitting spotify
+/// track get an alternative or end up unmatched.
+pub fn one_to_one(
+    spotify_tracks: &[TrackAlbumArtist],
+    results: &[Option<MatchResult>],
+    plex_tracks: &PlexIndex,
+    config: &MatchConfig,
+) -> Vec<Option<MatchResult>> {
+    // one row per distinct spotify track.
+    let mut rows: Vec<usize> = vec![];
+    let mut row_of: HashMap<&String, usize> = HashMap::new();
+    for (i, spotify_track) in spotify_tracks.iter().enumerate() {
+        if results[i].is_some() && !row_of.contains_key(spotify_track.id()) {
+            row_of.insert(spotify_track.id(), rows.len());
+            rows.push(i);
+        }
+    }
+
+    let originals: HashMap<&String, &TrackAlbumArtist> =
+        plex_tracks.tracks().iter().map(|p| (p.id(), p)).collect();
+    let mut columns: Vec<&TrackAlbumArtist> = vec![];
+    let mut column_of: HashMap<&String, usize> = HashMap::new();
+    // the weighted columns of each row.
+    let mut edges: Vec<HashMap<usize, f64>> = vec![];
+    for i in rows.iter() {
+        let spotify_track = &spotify_tracks[*i];
+        let picked = &results[*i].as_ref().unwrap().track;
+        // the pipeline may have picked a track with a transformed name, weigh the original.
+        let picked = originals.get(picked.id()).copied().unwrap_or(picked);
+        let mut row = HashMap::new();
+        let candidates = alternatives(spotify_track, plex_tracks, config);
+        for plex_track in std::iter::once(picked).chain(candidates) {
+            let column = *column_of.entry(plex_track.id()).or_insert_with(|| {
+                columns.push(plex_track);
+                columns.len() - 1
+            });
+            row.entry(column).or_insert_with(|| {
+                let bonus = if plex_track.id() == picked.id() {
+                    PICKED_BONUS
+                } else {
+                    0.0
+                };
+                weight(spotify_track, plex_track, config) + bonus
+            });
+        }
+        edges.push(row);
+    }
+
+    let assignment = assign_components(&edges, columns.len());
+    let mut reassigned = results.to_vec();
+    for (i, spotify_track) in spotify_tracks.iter().enumerate() {
+        let Some(row) = row_of.get(spotify_track.id()) else {
+            continue;
+        };
+        let picked = &results[i].as_ref().unwrap().track;
+        let picked = originals.get(picked.id()).copied().unwrap_or(picked);
+        match assignment[*row] {
+            Some(column) if columns[column].id() == picked.id() => {}
+            Some(column) => {
+                let plex_track = columns[column];
+                info!(
+                    "Reassigned: {} => {} instead of {}",
+                    MatchExplanation::describe(spotify_track),
+                    MatchExplanation::describe(plex_track),
+                    MatchExplanation::describe(picked)
+                );
+                let mut explanation = MatchExplanation::new(
+                    "one-to-one assignment",
+                    MatchExplanation::describe(spotify_track),
+                    MatchExplanation::describe(plex_track),
+                    edges[*row][&column],
+                );
+                explanation.matcher = "one_to_one".to_string();
+                explanation.transformations.push(format!(
+                    "\"{}\" went to a better fitting track",
+                    MatchExplanation::describe(picked)
+                ));
+                reassigned[i] = Some(MatchResult::new(plex_track, explanation));
+            }
+            None => {
+                info!(
+                    "Unmatched: {} lost {} to a better fitting track",
+                    MatchExplanation::describe(spotify_track),
+                    MatchExplanation::describe(picked)
+                );
+                reassigned[i] = None;
+            }
+        }
+    }
+    reassigned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plex(album: &str, rating_key: &str) -> TrackAlbumArtist {
+        TrackAlbumArtist {
+            track: "one more time".to_string(),
+            album: album.to_string(),
+            artist: vec!["daft punk".to_string()],
+            duration_ms: Some(320_000),
+            ..TrackAlbumArtist::plex(rating_key)
+        }
+    }
+
+    fn spotify(album: &str, id: &str) -> TrackAlbumArtist {
+        TrackAlbumArtist {
+            metadata: TrackAlbumArtist::spotify(id).metadata,
+            ..plex(album, "")
+        }
+    }
+
+    #[test]
+    fn test_one_to_one() {
+        let config = MatchConfig::default();
+        let plex_tracks = PlexIndex::new(
+            vec![plex("discovery", "1"), plex("musique vol. 1", "2")],
+            &config,
+        );
+        // the first one isn't on either album so the pipeline prefers the studio album,
+        // the third one is the same spotify track as the second.
+        let spotify_tracks = vec![
+            spotify("best of", "a"),
+            spotify("discovery", "b"),
+            spotify("discovery", "b"),
+        ];
+        let picked = MatchResult::new(
+            &plex_tracks.tracks()[0],
+            MatchExplanation::new("test", String::new(), String::new(), 1.0),
+        );
+        let results = vec![Some(picked.clone()), Some(picked.clone()), Some(picked)];
+
+        let results = one_to_one(&spotify_tracks, &results, &plex_tracks, &config);
+        let rating_keys: Vec<Option<&String>> = results
+            .iter()
+            .map(|r| r.as_ref().map(|r| r.track.id()))
+            .collect();
+        assert_eq!(
+            rating_keys,
+            vec![
+                Some(&"2".to_string()),
+                Some(&"1".to_string()),
+                Some(&"1".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assign() {
+        // greedy would give column 0 to row 0 and leave row 1 without a column.
+        let weights = vec![vec![Some(5.0), Some(4.0)], vec![Some(4.5), None]];
+        assert_eq!(assign(&weights), vec![Some(1), Some(0)]);
+
+        // only one of them can have it, the other stays unassigned.
+        let weights = vec![vec![Some(3.0)], vec![Some(5.0)], vec![]];
+        assert_eq!(assign(&weights), vec![None, Some(0), None]);
+
+        let weights = vec![
+            vec![Some(1.0), Some(2.0), Some(3.0)],
+            vec![Some(2.0), Some(4.0), Some(6.0)],
+            vec![Some(3.0), Some(6.0), Some(9.0)],
+        ];
+        assert_eq!(assign(&weights), vec![Some(0), Some(1), Some(2)]);
+        assert_eq!(assign(&[]), vec![]);
+    }
+}
@@ -1,76 +1,125 @@
-use std::collections::HashMap;
+use indexmap::IndexMap;
+use serde::Deserialize;
 
-use crate::{plex::client::Plex, track_album_artist::TrackAlbumArtist};
+use crate::{normalize::comparison_key, track_album_artist::TrackAlbumArtist};
 
-use super::{forward_backward::MatchForwardBack, levenshtein::LevenshteinDistance, Matcher};
+use super::{
+    forward_backward::MatchForwardBack, index::PlexIndex, levenshtein::LevenshteinDistance,
+    MatchConfig, MatchResult, Matcher,
+};
+
+/// Compares the comparison keys of the names, without punctuation, after applying
+/// the extra replacements.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchWithCharReplacements {
+    /// Replacements applied to the normalized names first, e.g. `{"+": " and "}`, in
+    /// the order of the config so that they can build on each other.
+    pub replacements: IndexMap<String, String>,
+    /// The configured matchers compared with the replacements applied.
+    #[serde(skip)]
+    pub forward_backward: MatchForwardBack,
+    #[serde(skip)]
+    pub levenshtein: LevenshteinDistance,
+}
+
+impl MatchWithCharReplacements {
+    fn key(&self, s: &str) -> String {
+        let replaced = self
+            .replacements
+            .iter()
+            .fold(s.to_string(), |s, (from, to)| s.replace(from, to));
+        comparison_key(&replaced)
+    }
+
+    fn describe(&self) -> String {
+        let mut description = String::from("comparison keys");
+        for (from, to) in self.replacements.iter() {
+            description.push_str(&format!(", \"{}\" replaced with \"{}\"", from, to));
+        }
+        description
+    }
+
+    fn replace(&self, track: &TrackAlbumArtist) -> TrackAlbumArtist {
+        let mut track = track.clone();
+        for artist in track
+            .artist
+            .iter_mut()
+            .chain(track.featured_artist.iter_mut())
+        {
+            *artist = self.key(artist);
+        }
+        track.track = self.key(&track.track);
+        track
+    }
+
+    /// Looks through copies of the given candidates with the replacements applied.
+    pub(super) fn find(
+        &self,
+        candidates: &[&TrackAlbumArtist],
+        spotify_track: &TrackAlbumArtist,
+        config: &MatchConfig,
+    ) -> Option<MatchResult> {
+        let replaced: Vec<TrackAlbumArtist> = candidates.iter().map(|p| self.replace(p)).collect();
+        let replaced: Vec<&TrackAlbumArtist> = replaced.iter().collect();
+        let spotify_track = self.replace(spotify_track);
+
+        let result = self
+            .forward_backward
+            .find(&replaced, &spotify_track, config)
+            .or_else(|| self.levenshtein.find(&replaced, &spotify_track, config))?;
+        Some(result.original(candidates).after(self.describe()))
+    }
+}
 
-pub struct MatchWithCharReplacements;
 #[async_trait::async_trait]
 impl Matcher for MatchWithCharReplacements {
     async fn match_fn(
         &self,
-        playlist_id: &mut String,
-        plex: &Plex,
-        plex_tracks: &Vec<TrackAlbumArtist>,
+        plex_tracks: &PlexIndex,
         spotify_track: &TrackAlbumArtist,
-        playlist_name: &String,
-    ) -> Result<TrackAlbumArtist, anyhow::Error> {
-        let replacements = HashMap::from([
-            ("’", "'"),
-            ("&", "and"),
-            ("-", " "),
-            ("(", ""),
-            (")", ""),
-            (".", ""),
-        ]);
+        config: &MatchConfig,
+    ) -> Result<MatchResult, anyhow::Error> {
+        let candidates = plex_tracks.candidates(spotify_track, config);
+        self.find(&candidates, spotify_track, config)
+            .ok_or_else(|| anyhow::anyhow!("No match found"))
+    }
+}
 
-        let new_plex_tracks: Vec<TrackAlbumArtist> = plex_tracks
-            .iter()
-            .map(|p| {
-                let mut track = p.clone();
-                for (from, to) in replacements.iter() {
-                    for artist in track.artist.iter_mut() {
-                        *artist = artist.replace(from, to);
-                    }
-                    track.track = track.track.replace(from, to);
-                }
-                track
-            })
-            .collect();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let mut spotify_track = spotify_track.clone();
-        for (from, to) in replacements.iter() {
-            for artist in spotify_track.artist.iter_mut() {
-                *artist = artist.replace(from, to);
-            }
-            spotify_track.track = spotify_track.track.replace(from, to);
-        }
+    #[tokio::test]
+    async fn test_original_track() {
+        let config = MatchConfig::default();
+        let plex_track = TrackAlbumArtist {
+            track: "t.n.t.".to_string(),
+            artist: vec!["ac/dc".to_string()],
+            ..TrackAlbumArtist::plex("1")
+        };
+        let plex_tracks = PlexIndex::new(vec![plex_track.clone()], &config);
+        let spotify_track = TrackAlbumArtist {
+            track: "tnt".to_string(),
+            artist: vec!["acdc".to_string()],
+            ..TrackAlbumArtist::spotify("a")
+        };
 
-        let result = MatchForwardBack {}
-            .match_fn(
-                playlist_id,
-                plex,
-                &new_plex_tracks,
-                &spotify_track,
-                playlist_name,
-            )
-            .await;
-        if result.is_ok() {
-            return result;
-        }
-        let result = LevenshteinDistance {}
-            .match_fn(
-                playlist_id,
-                plex,
-                &new_plex_tracks,
-                &spotify_track,
-                playlist_name,
-            )
-            .await;
-        if result.is_ok() {
-            return result;
-        }
+        let result = MatchWithCharReplacements::default()
+            .match_fn(&plex_tracks, &spotify_track, &config)
+            .await
+            .unwrap();
+        // compared by their keys, but reported as they are in plex.
+        assert_eq!(result.explanation.plex, "acdc - tnt");
+        assert_eq!(result.track, plex_track);
+    }
 
-        Err(anyhow::anyhow!("No match found"))
+    #[test]
+    fn test_replacement_order() {
+        // applied as declared, the second one builds on the first.
+        let matcher: MatchWithCharReplacements =
+            serde_json::from_str(r#"{"replacements": {"+": " and ", "&": "+"}}"#).unwrap();
+        assert_eq!(matcher.key("rock & roll"), comparison_key("rock + roll"));
+        assert_ne!(matcher.key("rock & roll"), comparison_key("rock and roll"));
     }
 }
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+
+use crate::track_album_artist::TrackAlbumArtist;
+
+use super::MatchResult;
+
+/// What to do when different spotify tracks are matched to the same plex track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DuplicatePolicy {
+    /// Add the plex track for each of them.
+    Allow,
+    /// Add none of them, the match is ambiguous.
+    Skip,
+    /// Only add it for the first one.
+    KeepFirst,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Duplicate {
+    /// The first spotify track matched to its plex track.
+    No,
+    /// The same spotify track again, it is in the playlist several times.
+    Repeated { first: usize },
+    /// A different spotify track matched to the same plex track as `first`.
+    Collision { first: usize },
+}
+
+/// Whether each match is a duplicate of an earlier one.
+pub fn find_duplicates(
+    spotify_tracks: &[TrackAlbumArtist],
+    results: &[Option<MatchResult>],
+) -> Vec<Duplicate> {
+    let mut first_of: HashMap<&String, usize> = HashMap::new();
+    let mut first_match_of: HashMap<(&String, &String), usize> = HashMap::new();
+    spotify_tracks
+        .iter()
+        .zip(results)
+        .enumerate()
+        .map(|(i, (spotify_track, result))| {
+            let Some(result) = result else {
+                return Duplicate::No;
+            };
+            // a repeat is one whatever its first occurrence collided with.
+            let repeated = *first_match_of
+                .entry((spotify_track.id(), result.track.id()))
+                .or_insert(i);
+            let first = *first_of.entry(result.track.id()).or_insert(i);
+            if repeated != i {
+                Duplicate::Repeated { first: repeated }
+            } else if first != i {
+                Duplicate::Collision { first }
+            } else {
+                Duplicate::No
+            }
+        })
+        .collect()
+}
+
+/// Which of the matches are added to the playlist. Spotify tracks that are in the
+/// playlist several times are added as many times, unless their plex track is
+/// skipped altogether.
+pub fn apply_policy(duplicates: &[Duplicate], policy: DuplicatePolicy) -> Vec<bool> {
+    let collided: Vec<usize> = duplicates
+        .iter()
+        .filter_map(|d| match d {
+            Duplicate::Collision { first } => Some(*first),
+            _ => None,
+        })
+        .collect();
+    let mut keep: Vec<bool> = Vec::with_capacity(duplicates.len());
+    for (i, duplicate) in duplicates.iter().enumerate() {
+        keep.push(match (policy, duplicate) {
+            (DuplicatePolicy::Allow, _) => true,
+            // repeats follow their first occurrence.
+            (_, Duplicate::Repeated { first }) => keep[*first],
+            (_, Duplicate::Collision { .. }) => false,
+            (DuplicatePolicy::KeepFirst, Duplicate::No) => true,
+            (DuplicatePolicy::Skip, Duplicate::No) => !collided.contains(&i),
+        });
+    }
+    keep
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plex::matcher::explain::MatchExplanation;
+
+    #[test]
+    fn test_duplicates() {
+        // spotify track and the rating key of its match.
+        let matches = [
+            ("a", Some("1")),
+            ("b", Some("2")),
+            ("a", Some("1")),
+            ("c", Some("2")),
+            ("d", None),
+            ("e", Some("3")),
+            ("e", Some("3")),
+            // a repeat of a track that collided.
+            ("c", Some("2")),
+        ];
+        let spotify_tracks: Vec<TrackAlbumArtist> = matches
+            .iter()
+            .map(|(uri, _)| TrackAlbumArtist::spotify(uri))
+            .collect();
+        let results: Vec<Option<MatchResult>> = matches
+            .iter()
+            .map(|(_, rating_key)| {
+                rating_key.map(|rating_key| {
+                    let plex_track = TrackAlbumArtist::plex(rating_key);
+                    let explanation =
+                        MatchExplanation::new("test", String::new(), String::new(), 1.0);
+                    MatchResult::new(&plex_track, explanation)
+                })
+            })
+            .collect();
+
+        let duplicates = find_duplicates(&spotify_tracks, &results);
+        assert_eq!(
+            duplicates,
+            vec![
+                Duplicate::No,
+                Duplicate::No,
+                Duplicate::Repeated { first: 0 },
+                Duplicate::Collision { first: 1 },
+                Duplicate::No,
+                Duplicate::No,
+                Duplicate::Repeated { first: 5 },
+                Duplicate::Repeated { first: 3 },
+            ]
+        );
+
+        let cases = [
+            (
+                DuplicatePolicy::Allow,
+                [true, true, true, true, true, true, true, true],
+            ),
+            (
+                DuplicatePolicy::Skip,
+                [true, false, true, false, true, true, true, false],
+            ),
+            (
+                DuplicatePolicy::KeepFirst,
+                [true, true, true, false, true, true, true, false],
+            ),
+        ];
+        for (policy, expected) in cases {
+            assert_eq!(apply_policy(&duplicates, policy), expected, "{:?}", policy);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::track_album_artist::TrackAlbumArtist;
+
+use super::MatchConfig;
+
+/// Durations this close are considered the same recording.
+const CLOSE_DURATION_MS: u64 = 2_000;
+/// Extra edits the levenshtein matcher allows when the durations are close.
+const CLOSE_DURATION_ALLOWANCE: usize = 2;
+
+fn difference_ms(spotify_track: &TrackAlbumArtist, plex_track: &TrackAlbumArtist) -> Option<u64> {
+    match (spotify_track.duration_ms, plex_track.duration_ms) {
+        (Some(a), Some(b)) => Some(a.abs_diff(b)),
+        _ => None,
+    }
+}
+
+/// Whether the plex track could be the same recording, a radio edit and an extended mix
+/// are several minutes apart. Tracks without a known duration are never rejected.
+pub fn within_tolerance(
+    spotify_track: &TrackAlbumArtist,
+    plex_track: &TrackAlbumArtist,
+    config: &MatchConfig,
+) -> bool {
+    match (
+        difference_ms(spotify_track, plex_track),
+        config.duration_tolerance_ms,
+    ) {
+        (Some(difference), Some(tolerance)) => difference <= tolerance,
+        _ => true,
+    }
+}
+
+/// How close the durations are, from 1.0 (identical) to 0.0 (at the tolerance or unknown).
+pub fn duration_similarity(
+    spotify_track: &TrackAlbumArtist,
+    plex_track: &TrackAlbumArtist,
+    config: &MatchConfig,
+) -> f64 {
+    let Some(difference) = difference_ms(spotify_track, plex_track) else {
+        return 0.0;
+    };
+    let tolerance = config
+        .duration_tolerance_ms
+        .unwrap_or(CLOSE_DURATION_MS * 5)
+        .max(1);
+    1.0 - (difference.min(tolerance) as f64 / tolerance as f64)
+}
+
+/// Additional edit distance accepted for candidates whose duration matches almost exactly.
+pub fn distance_allowance(
+    spotify_track: &TrackAlbumArtist,
+    plex_track: &TrackAlbumArtist,
+) -> usize {
+    match difference_ms(spotify_track, plex_track) {
+        Some(difference) if difference <= CLOSE_DURATION_MS => CLOSE_DURATION_ALLOWANCE,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(duration_ms: Option<u64>) -> TrackAlbumArtist {
+        TrackAlbumArtist {
+            duration_ms,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_within_tolerance() {
+        let config = MatchConfig::default();
+        let spotify_track = track(Some(200_000));
+        let cases = [
+            (Some(200_000), true),
+            (Some(210_000), true),
+            (Some(189_999), false),
+            (Some(500_000), false),
+            (None, true),
+        ];
+        for (duration_ms, expected) in cases {
+            assert_eq!(
+                within_tolerance(&spotify_track, &track(duration_ms), &config),
+                expected,
+                "{:?}",
+                duration_ms
+            );
+        }
+
+        let disabled = MatchConfig {
+            duration_tolerance_ms: None,
+            ..Default::default()
+        };
+        assert!(within_tolerance(
+            &spotify_track,
+            &track(Some(500_000)),
+            &disabled
+        ));
+    }
+
+    #[test]
+    fn test_duration_similarity() {
+        let config = MatchConfig::default();
+        let spotify_track = track(Some(200_000));
+        let cases = [
+            (Some(200_000), 1.0),
+            (Some(195_000), 0.5),
+            (Some(210_000), 0.0),
+            (Some(300_000), 0.0),
+            (None, 0.0),
+        ];
+        for (duration_ms, expected) in cases {
+            assert_eq!(
+                duration_similarity(&spotify_track, &track(duration_ms), &config),
+                expected,
+                "{:?}",
+                duration_ms
+            );
+        }
+    }
+
+    #[test]
+    fn test_distance_allowance() {
+        let spotify_track = track(Some(200_000));
+        let cases = [
+            (Some(200_000), CLOSE_DURATION_ALLOWANCE),
+            (Some(202_000), CLOSE_DURATION_ALLOWANCE),
+            (Some(202_001), 0),
+            (None, 0),
+        ];
+        for (duration_ms, expected) in cases {
+            assert_eq!(
+                distance_allowance(&spotify_track, &track(duration_ms)),
+                expected,
+                "{:?}",
+                duration_ms
+            );
+        }
+        assert_eq!(distance_allowance(&track(None), &track(Some(200_000))), 0);
+    }
+}
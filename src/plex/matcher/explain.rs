@@ -0,0 +1,61 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::track_album_artist::TrackAlbumArtist;
+
+/// Why a spotify track was matched to a plex track.
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchExplanation {
+    /// The pipeline step that found the match.
+    pub matcher: String,
+    /// How the two sides were compared, e.g. "isrc" or "levenshtein".
+    pub method: String,
+    /// What was compared, after normalization and the transformations.
+    pub spotify: String,
+    pub plex: String,
+    /// Changes made to both sides before comparing, in the order they were applied.
+    pub transformations: Vec<String>,
+    /// Depends on the method: the edit distance for levenshtein, the similarity
+    /// between 0.0 and 1.0 otherwise.
+    pub score: f64,
+    /// Matched to another version of the song as a last resort, e.g. a remix for the
+    /// acoustic version, so it is worth checking.
+    pub other_version: bool,
+}
+
+impl MatchExplanation {
+    pub fn new(method: &str, spotify: String, plex: String, score: f64) -> Self {
+        Self {
+            matcher: String::new(),
+            method: method.to_string(),
+            spotify,
+            plex,
+            transformations: vec![],
+            score,
+            other_version: false,
+        }
+    }
+
+    /// "artist, artist - title" as the matchers see a track.
+    pub fn describe(track: &TrackAlbumArtist) -> String {
+        format!("{} - {}", track.artist.join(", "), track.track)
+    }
+}
+
+impl fmt::Display for MatchExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] \"{}\" => \"{}\" by {} (score {:.2})",
+            self.matcher, self.spotify, self.plex, self.method, self.score
+        )?;
+        if !self.transformations.is_empty() {
+            write!(f, " after {}", self.transformations.join(", "))?;
+        }
+        if self.other_version {
+            write!(f, ", other version")?;
+        }
+        Ok(())
+    }
+}
@@ -1,40 +1,152 @@
-use crate::{
-    plex::{client::Plex, playlist},
-    track_album_artist::TrackAlbumArtist,
+use crate::track_album_artist::TrackAlbumArtist;
+
+use super::{
+    duration::within_tolerance, explain::MatchExplanation, index::PlexIndex,
+    ranking::best_candidate, version::compatible, MatchConfig, MatchResult, Matcher,
 };
+use serde::Deserialize;
 
-use super::Matcher;
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchForwardBack {
+    /// Also accept other versions of the song, e.g. a remix for the acoustic version.
+    pub any_version: bool,
+}
 
-pub struct MatchForwardBack;
-#[async_trait::async_trait]
-impl Matcher for MatchForwardBack {
-    async fn match_fn(
+impl MatchForwardBack {
+    /// Looks through the given candidates, the matchers comparing transformed copies
+    /// pass theirs.
+    pub(super) fn find(
         &self,
-        playlist_id: &mut String,
-        plex: &Plex,
-        plex_tracks: &Vec<TrackAlbumArtist>,
+        candidates: &[&TrackAlbumArtist],
         spotify_track: &TrackAlbumArtist,
-        playlist_name: &String,
-    ) -> Result<TrackAlbumArtist, anyhow::Error> {
-        for plex_track in plex_tracks.iter() {
+        config: &MatchConfig,
+    ) -> Option<MatchResult> {
+        let spotify_artists: Vec<String> = spotify_track
+            .credited_artists()
+            .map(|a| config.artist_aliases.canonical(a))
+            .collect();
+
+        let mut matching: Vec<&TrackAlbumArtist> = Vec::new();
+        'tracks: for plex_track in candidates.iter().copied() {
+            // a different length means a different version of the song.
+            if !within_tolerance(spotify_track, plex_track, config)
+                || (!self.any_version && !compatible(spotify_track, plex_track))
+            {
+                continue;
+            }
+            let forward = plex_track.track.starts_with(&spotify_track.track);
+            let backward = spotify_track.track.starts_with(&plex_track.track);
+            if !forward && !backward {
+                continue;
+            }
             // loop through each artist in the plex track.
-            for plex_artist in plex_track.artist.iter() {
+            for plex_artist in plex_track.credited_artists() {
+                let plex_artist = config.artist_aliases.canonical(plex_artist);
                 // loop through each artist in the spotify track.
-                for spotify_artist in spotify_track.artist.iter() {
+                for spotify_artist in spotify_artists.iter() {
                     // if the plex artist starts with the spotify artist or vice versa
                     // and the plex track starts with the spotify track or vice versa
-                    // then add the plex track to the playlist and return the plex track.
-                    if (plex_artist.starts_with(spotify_artist)
-                        && plex_track.track.starts_with(&spotify_track.track))
-                        || (spotify_artist.starts_with(plex_artist)
-                            && spotify_track.track.starts_with(&plex_track.track))
+                    // then the plex track is a candidate.
+                    if (forward && plex_artist.starts_with(spotify_artist))
+                        || (backward && spotify_artist.starts_with(&plex_artist))
                     {
-                        playlist(playlist_id, &plex, plex_track, playlist_name).await?;
-                        return Ok(plex_track.clone());
+                        matching.push(plex_track);
+                        continue 'tracks;
                     }
                 }
             }
         }
-        Err(anyhow::anyhow!("No match found"))
+
+        // the same song can be on several releases, prefer the one closest to spotify's.
+        let plex_track = best_candidate(&matching, spotify_track, config)?;
+        // how much of the longer title the shorter one covers.
+        let (a, b) = (
+            spotify_track.track.chars().count(),
+            plex_track.track.chars().count(),
+        );
+        let explanation = MatchExplanation::new(
+            "title and artist prefix",
+            MatchExplanation::describe(spotify_track),
+            MatchExplanation::describe(plex_track),
+            a.min(b) as f64 / a.max(b).max(1) as f64,
+        );
+        Some(MatchResult::new(plex_track, explanation))
+    }
+}
+
+#[async_trait::async_trait]
+impl Matcher for MatchForwardBack {
+    async fn match_fn(
+        &self,
+        plex_tracks: &PlexIndex,
+        spotify_track: &TrackAlbumArtist,
+        config: &MatchConfig,
+    ) -> Result<MatchResult, anyhow::Error> {
+        let candidates = plex_tracks.candidates(spotify_track, config);
+        self.find(&candidates, spotify_track, config)
+            .ok_or_else(|| anyhow::anyhow!("No match found"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(rating_key: &str, artist: &str, featured: &[&str]) -> TrackAlbumArtist {
+        TrackAlbumArtist {
+            track: "under pressure".to_string(),
+            artist: vec![artist.to_string()],
+            featured_artist: featured.iter().map(|f| f.to_string()).collect(),
+            ..TrackAlbumArtist::plex(rating_key)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_featured_artists() {
+        let config = MatchConfig::default();
+        // filed under the guest in plex.
+        let plex_tracks = PlexIndex::new(vec![track("1", "david bowie", &[])], &config);
+        let spotify_track = track("", "queen", &["david bowie"]);
+        let result = MatchForwardBack::default()
+            .match_fn(&plex_tracks, &spotify_track, &config)
+            .await
+            .unwrap();
+        assert_eq!(result.track.id(), "1");
+
+        // and the other way around.
+        let plex_tracks = PlexIndex::new(vec![track("2", "queen", &["david bowie"])], &config);
+        let spotify_track = track("", "david bowie", &[]);
+        let result = MatchForwardBack::default()
+            .match_fn(&plex_tracks, &spotify_track, &config)
+            .await
+            .unwrap();
+        assert_eq!(result.track.id(), "2");
+
+        let spotify_track = track("", "vanilla ice", &[]);
+        assert!(MatchForwardBack::default()
+            .match_fn(&plex_tracks, &spotify_track, &config)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_score() {
+        let config = MatchConfig::default();
+        let plex_track = TrackAlbumArtist {
+            track: "夜に駆ける acoustic".to_string(),
+            ..track("1", "yoasobi", &[])
+        };
+        let plex_tracks = PlexIndex::new(vec![plex_track], &config);
+        let spotify_track = TrackAlbumArtist {
+            track: "夜に駆ける".to_string(),
+            ..track("", "yoasobi", &[])
+        };
+        let result = MatchForwardBack::default()
+            .match_fn(&plex_tracks, &spotify_track, &config)
+            .await
+            .unwrap();
+        // characters, not the bytes of the kana and kanji.
+        assert_eq!(result.explanation.score, 5.0 / 14.0);
     }
 }
@@ -0,0 +1,381 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{normalize::comparison_key, track_album_artist::TrackAlbumArtist};
+
+use super::{
+    remove_sections::without_sections,
+    transliteration::{compact, is_non_latin, romanize_track},
+    MatchConfig,
+};
+
+/// Trigrams of a string, padded so that strings sharing a prefix always share one and
+/// strings shorter than three characters still have some.
+fn trigrams(s: &str) -> HashSet<String> {
+    let padded: Vec<char> = format!("  {} ", s).chars().collect();
+    padded.windows(3).map(|w| w.iter().collect()).collect()
+}
+
+/// The credited artists and titles a track is looked up by: their comparison keys with the
+/// artist aliases resolved, plus the forms the wrapping matchers compare, i.e. without
+/// sections, romanized for tracks in non-latin scripts and without spaces like the
+/// romanized names.
+fn keys(track: &TrackAlbumArtist, config: &MatchConfig) -> (Vec<String>, Vec<String>) {
+    let mut artists: Vec<String> = track
+        .credited_artists()
+        .flat_map(|a| [a.clone(), without_sections(a)])
+        .map(|a| config.artist_aliases.canonical(&a))
+        .collect();
+    let mut titles = vec![
+        comparison_key(&track.track),
+        comparison_key(&without_sections(&track.track)),
+    ];
+    if is_non_latin(track) {
+        let romanized = romanize_track(track, config);
+        artists.extend(romanized.artist);
+        artists.extend(romanized.featured_artist);
+        titles.push(romanized.track);
+    }
+    let compacted: Vec<String> = artists.iter().map(|a| compact(a)).collect();
+    artists.extend(compacted);
+    for keys in [&mut artists, &mut titles] {
+        keys.retain(|k| !k.is_empty());
+        keys.sort();
+        keys.dedup();
+    }
+    (artists, titles)
+}
+
+/// How many tracks with a similar title are still candidates when no artist is alike.
+const SHORTLIST_SIZE: usize = 20;
+
+/// Lookup tables over the plex library, built once so that matchers only look at
+/// tracks that have a chance of matching instead of the whole library.
+pub struct PlexIndex {
+    tracks: Vec<TrackAlbumArtist>,
+    by_artist: HashMap<String, Vec<usize>>,
+    by_title_trigram: HashMap<String, Vec<usize>>,
+    title_trigram_count: Vec<usize>,
+    artist_trigrams: Vec<HashSet<String>>,
+    by_isrc: HashMap<String, Vec<usize>>,
+    by_musicbrainz_id: HashMap<String, Vec<usize>>,
+    /// Every track is a candidate, to compare against the index.
+    full_scan: bool,
+}
+
+impl PlexIndex {
+    pub fn new(tracks: Vec<TrackAlbumArtist>, config: &MatchConfig) -> Self {
+        let mut index = Self {
+            tracks: vec![],
+            by_artist: HashMap::new(),
+            by_title_trigram: HashMap::new(),
+            title_trigram_count: vec![],
+            artist_trigrams: vec![],
+            by_isrc: HashMap::new(),
+            by_musicbrainz_id: HashMap::new(),
+            full_scan: false,
+        };
+        for (i, track) in tracks.iter().enumerate() {
+            let (artists, titles) = keys(track, config);
+            let mut artist_trigrams = HashSet::new();
+            for artist in artists {
+                artist_trigrams.extend(trigrams(&artist));
+                index.by_artist.entry(artist).or_default().push(i);
+            }
+            index.artist_trigrams.push(artist_trigrams);
+
+            let title_trigrams: HashSet<String> = titles.iter().flat_map(|t| trigrams(t)).collect();
+            index.title_trigram_count.push(title_trigrams.len());
+            for trigram in title_trigrams {
+                index.by_title_trigram.entry(trigram).or_default().push(i);
+            }
+
+            if let Some(isrc) = &track.isrc {
+                index.by_isrc.entry(isrc.clone()).or_default().push(i);
+            }
+            for mbid in track.musicbrainz_ids.iter() {
+                index
+                    .by_musicbrainz_id
+                    .entry(mbid.clone())
+                    .or_default()
+                    .push(i);
+            }
+        }
+        index.tracks = tracks;
+        index
+    }
+
+    /// An index looking through the whole library for every track, like the matchers
+    /// did before there was one.
+    #[cfg(test)]
+    pub fn full_scan(tracks: Vec<TrackAlbumArtist>, config: &MatchConfig) -> Self {
+        Self {
+            full_scan: true,
+            ..Self::new(tracks, config)
+        }
+    }
+
+    pub fn tracks(&self) -> &Vec<TrackAlbumArtist> {
+        &self.tracks
+    }
+
+    fn get(&self, ids: impl IntoIterator<Item = usize>) -> Vec<&TrackAlbumArtist> {
+        let mut ids: Vec<usize> = ids.into_iter().collect();
+        // keep the order of the library, matchers take the first of equal candidates.
+        ids.sort_unstable();
+        ids.dedup();
+        ids.into_iter().map(|i| &self.tracks[i]).collect()
+    }
+
+    /// Tracks by the same artist, or sharing at least half the title trigrams of the
+    /// shorter title and part of an artist. When none are, the few tracks sharing the
+    /// most title trigrams, so that names the keys don't cover, e.g. with custom
+    /// replacements, can still match without comparing against the whole library.
+    pub fn candidates(
+        &self,
+        track: &TrackAlbumArtist,
+        config: &MatchConfig,
+    ) -> Vec<&TrackAlbumArtist> {
+        if self.full_scan {
+            return self.tracks.iter().collect();
+        }
+        let (artists, titles) = keys(track, config);
+        let artist_trigrams: HashSet<String> = artists.iter().flat_map(|a| trigrams(a)).collect();
+        let title_trigrams: HashSet<String> = titles.iter().flat_map(|t| trigrams(t)).collect();
+
+        let same_artist = artists
+            .iter()
+            .filter_map(|a| self.by_artist.get(a))
+            .flatten()
+            .copied();
+
+        let mut shared: HashMap<usize, usize> = HashMap::new();
+        for i in title_trigrams
+            .iter()
+            .filter_map(|t| self.by_title_trigram.get(t))
+            .flatten()
+        {
+            *shared.entry(*i).or_default() += 1;
+        }
+        let similar_titles: Vec<(usize, usize)> = shared
+            .into_iter()
+            .filter(|(i, count)| {
+                *count * 2 >= self.title_trigram_count[*i].min(title_trigrams.len())
+            })
+            .collect();
+        let similar_title = similar_titles
+            .iter()
+            .filter(|(i, _)| !self.artist_trigrams[*i].is_disjoint(&artist_trigrams))
+            .map(|(i, _)| *i);
+
+        let candidates = self.get(same_artist.chain(similar_title));
+        if !candidates.is_empty() {
+            return candidates;
+        }
+        let mut shortlist = similar_titles;
+        shortlist.sort_unstable_by_key(|(i, count)| (std::cmp::Reverse(*count), *i));
+        self.get(shortlist.into_iter().take(SHORTLIST_SIZE).map(|(i, _)| i))
+    }
+
+    pub fn by_isrc(&self, isrc: &str) -> Vec<&TrackAlbumArtist> {
+        self.get(self.by_isrc.get(isrc).into_iter().flatten().copied())
+    }
+
+    /// Whether any track is tagged with a MusicBrainz recording id.
+    pub fn has_musicbrainz_ids(&self) -> bool {
+        !self.by_musicbrainz_id.is_empty()
+    }
+
+    pub fn by_musicbrainz_ids(&self, mbids: &[String]) -> Vec<&TrackAlbumArtist> {
+        self.get(
+            mbids
+                .iter()
+                .filter_map(|id| self.by_musicbrainz_id.get(id))
+                .flatten()
+                .copied(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use levenshtein::levenshtein;
+
+    use super::*;
+    use crate::plex::matcher::{
+        pipeline::Pipeline,
+        registry::{default_matchers, MatcherRegistry},
+    };
+
+    const WORDS: [&str; 24] = [
+        "love", "night", "heart", "fire", "dream", "summer", "rain", "city", "light", "dance",
+        "blue", "gold", "river", "shadow", "star", "wild", "home", "storm", "ocean", "silver",
+        "ghost", "paper", "sweet", "broken",
+    ];
+
+    fn track(i: usize, artist: String, title: String) -> TrackAlbumArtist {
+        TrackAlbumArtist {
+            track: title,
+            artist: vec![artist],
+            ..TrackAlbumArtist::plex(&i.to_string())
+        }
+    }
+
+    /// A deterministic library of `size` tracks by `size / 10` artists.
+    fn library(size: usize) -> Vec<TrackAlbumArtist> {
+        let mut seed: usize = 42;
+        let mut word = || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            WORDS[(seed >> 33) % WORDS.len()]
+        };
+        (0..size)
+            .map(|i| {
+                let artist = format!("{} {} {}", word(), word(), i / 10);
+                let title = format!("{} {} {} {}", word(), word(), word(), i);
+                track(i, artist, title)
+            })
+            .collect()
+    }
+
+    fn closest<'a>(
+        spotify_track: &TrackAlbumArtist,
+        plex_tracks: impl IntoIterator<Item = &'a TrackAlbumArtist>,
+    ) -> Option<&'a TrackAlbumArtist> {
+        plex_tracks
+            .into_iter()
+            .map(|p| {
+                let distance = levenshtein(&spotify_track.artist[0], &p.artist[0])
+                    + levenshtein(&spotify_track.track, &p.track);
+                (distance, p)
+            })
+            .filter(|(distance, _)| *distance <= 4)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, p)| p)
+    }
+
+    #[test]
+    fn test_candidates() {
+        let config = MatchConfig::default();
+        let index = PlexIndex::new(library(1000), &config);
+        let mut spotify_track = index.tracks()[123].clone();
+        spotify_track.track = spotify_track.track.replacen(' ', "  ", 1) + "x";
+        let candidates = index.candidates(&spotify_track, &config);
+        assert!(candidates.len() < 100);
+        assert_eq!(
+            closest(&spotify_track, candidates).map(|p| &p.metadata),
+            Some(&index.tracks()[123].metadata)
+        );
+    }
+
+    #[test]
+    fn test_candidate_variants() {
+        let config = MatchConfig {
+            transliterations: HashMap::from([(
+                "坂本龍一".to_string(),
+                "ryuichi sakamoto".to_string(),
+            )]),
+            ..Default::default()
+        };
+        let mut plex_tracks = library(1000);
+        plex_tracks.push(track(
+            1000,
+            "坂本龍一".to_string(),
+            "戦場のメリークリスマス".to_string(),
+        ));
+        let index = PlexIndex::new(plex_tracks, &config);
+
+        // romanized on spotify, found by the compact romanized name.
+        let spotify_track = track(
+            0,
+            "ryuichi sakamoto".to_string(),
+            "merry christmas mr. lawrence".to_string(),
+        );
+        let candidates = index.candidates(&spotify_track, &config);
+        assert!(candidates.len() < 100);
+        assert!(candidates.iter().any(|c| c.id() == "1000"));
+
+        // an artist that isn't in the library, only a few tracks of the same title.
+        let mut spotify_track = index.tracks()[123].clone();
+        spotify_track.artist = vec!["zz".to_string()];
+        let candidates = index.candidates(&spotify_track, &config);
+        assert!(candidates.len() <= SHORTLIST_SIZE);
+        assert!(candidates.iter().any(|c| c.id() == "123"));
+
+        // nothing alike.
+        let spotify_track = track(0, "zz".to_string(), "qq".to_string());
+        assert!(index.candidates(&spotify_track, &config).is_empty());
+    }
+
+    /// Matches every spotify track with the pipeline, returning the rating keys found
+    /// and how long it took.
+    async fn match_all(
+        pipeline: &Pipeline,
+        plex_tracks: &PlexIndex,
+        spotify_tracks: &[TrackAlbumArtist],
+        config: &MatchConfig,
+    ) -> (Vec<Option<String>>, Duration) {
+        let start = Instant::now();
+        let mut found = vec![];
+        for spotify_track in spotify_tracks {
+            let result = pipeline.run(plex_tracks, spotify_track, config).await;
+            found.push(result.map(|r| r.track.id().clone()));
+        }
+        (found, start.elapsed())
+    }
+
+    /// `cargo test --release bench_pipeline -- --ignored --nocapture`
+    #[tokio::test]
+    #[ignore]
+    async fn bench_pipeline() {
+        let config = MatchConfig::default();
+        let pipeline = MatcherRegistry::default()
+            .build(&default_matchers(None))
+            .unwrap();
+        let plex_tracks = library(10_000);
+        // every 100th track as it is, slightly different, not in the library or by an
+        // artist that isn't in the library.
+        let spotify_tracks: Vec<TrackAlbumArtist> = plex_tracks
+            .iter()
+            .step_by(100)
+            .enumerate()
+            .map(|(i, t)| {
+                let mut t = TrackAlbumArtist {
+                    metadata: TrackAlbumArtist::spotify(&i.to_string()).metadata,
+                    ..t.clone()
+                };
+                match i % 4 {
+                    0 => {}
+                    1 => t.track.insert(t.track.len() / 2, 'x'),
+                    2 => t.track = format!("missing track {}", i),
+                    _ => t.artist = vec![format!("missing artist {}", i)],
+                }
+                t
+            })
+            .collect();
+
+        let start = Instant::now();
+        let scan = PlexIndex::full_scan(plex_tracks.clone(), &config);
+        let scan_build_time = start.elapsed();
+        let (scanned, scan_time) = match_all(&pipeline, &scan, &spotify_tracks, &config).await;
+
+        let start = Instant::now();
+        let index = PlexIndex::new(plex_tracks, &config);
+        let build_time = start.elapsed();
+        let (indexed, lookup_time) = match_all(&pipeline, &index, &spotify_tracks, &config).await;
+
+        println!(
+            "{} spotify tracks against {} plex tracks: full scan {:?} (+ {:?} to build), index {:?} + matching {:?} ({:.1}x faster)",
+            spotify_tracks.len(),
+            index.tracks().len(),
+            scan_time,
+            scan_build_time,
+            build_time,
+            lookup_time,
+            scan_time.as_secs_f64() / (build_time + lookup_time).as_secs_f64()
+        );
+        assert_eq!(scanned, indexed);
+    }
+}
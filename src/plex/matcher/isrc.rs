@@ -0,0 +1,97 @@
+use crate::track_album_artist::TrackAlbumArtist;
+
+use super::{
+    explain::MatchExplanation, index::PlexIndex, ranking::best_candidate, MatchConfig, MatchResult,
+    Matcher,
+};
+
+/// Exact match on the ISRC, which identifies a recording regardless of how it is titled.
+pub struct MatchIsrc;
+#[async_trait::async_trait]
+impl Matcher for MatchIsrc {
+    async fn match_fn(
+        &self,
+        plex_tracks: &PlexIndex,
+        spotify_track: &TrackAlbumArtist,
+        config: &MatchConfig,
+    ) -> Result<MatchResult, anyhow::Error> {
+        let Some(isrc) = &spotify_track.isrc else {
+            return Err(anyhow::anyhow!("No ISRC for spotify track"));
+        };
+
+        let candidates = plex_tracks.by_isrc(isrc);
+
+        // the same recording can be on several releases, prefer the one closest to spotify's.
+        if let Some(plex_track) = best_candidate(&candidates, spotify_track, config) {
+            let explanation = MatchExplanation::new("isrc", isrc.clone(), isrc.clone(), 1.0);
+            return Ok(MatchResult::new(plex_track, explanation));
+        }
+        Err(anyhow::anyhow!("No match found"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(rating_key: &str, album: &str, isrc: Option<&str>) -> TrackAlbumArtist {
+        TrackAlbumArtist {
+            track: "hurt".to_string(),
+            album: album.to_string(),
+            artist: vec!["johnny cash".to_string()],
+            isrc: isrc.map(|i| i.to_string()),
+            ..TrackAlbumArtist::plex(rating_key)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_match_isrc() {
+        let config = MatchConfig::default();
+        let plex_tracks = PlexIndex::new(
+            vec![
+                track("1", "the essential johnny cash", Some("USUM70200839")),
+                track(
+                    "2",
+                    "american iv: the man comes around",
+                    Some("USUM70200839"),
+                ),
+                track("3", "american iv: the man comes around", None),
+                track("4", "unearthed", Some("USUM70300001")),
+            ],
+            &config,
+        );
+
+        // the same recording is on two albums, the one spotify has wins.
+        let spotify_track = track(
+            "",
+            "american iv: the man comes around",
+            Some("USUM70200839"),
+        );
+        let result = MatchIsrc
+            .match_fn(&plex_tracks, &spotify_track, &config)
+            .await
+            .unwrap();
+        assert_eq!(result.track.id(), "2");
+
+        // a different title doesn't matter, only the isrc does.
+        let mut spotify_track = track("", "the essential johnny cash", Some("USUM70300001"));
+        spotify_track.track = "hurt (live)".to_string();
+        let result = MatchIsrc
+            .match_fn(&plex_tracks, &spotify_track, &config)
+            .await
+            .unwrap();
+        assert_eq!(result.track.id(), "4");
+
+        for isrc in [Some("GBAYE0000001"), None] {
+            let spotify_track = track("", "american iv: the man comes around", isrc);
+            assert!(
+                MatchIsrc
+                    .match_fn(&plex_tracks, &spotify_track, &config)
+                    .await
+                    .is_err(),
+                "{:?}",
+                isrc
+            );
+        }
+    }
+}
@@ -1,75 +1,158 @@
-use crate::{
-    plex::{client::Plex, playlist},
-    track_album_artist::TrackAlbumArtist,
-};
+use crate::track_album_artist::TrackAlbumArtist;
 
-use super::Matcher;
+use super::{
+    duration::{distance_allowance, within_tolerance},
+    explain::MatchExplanation,
+    index::PlexIndex,
+    ranking::best_candidate,
+    version::compatible,
+    MatchConfig, MatchResult, Matcher,
+};
 use levenshtein::levenshtein;
+use log::info;
+use serde::Deserialize;
 
-pub struct LevenshteinDistance;
-#[async_trait::async_trait]
-impl Matcher for LevenshteinDistance {
-    async fn match_fn(
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LevenshteinDistance {
+    /// Also accept other versions of the song, e.g. a remix for the acoustic version.
+    pub any_version: bool,
+    /// Largest combined edit distance of the artist and the title that still matches.
+    pub max_distance: usize,
+}
+
+impl Default for LevenshteinDistance {
+    fn default() -> Self {
+        Self {
+            any_version: false,
+            max_distance: 4,
+        }
+    }
+}
+
+impl LevenshteinDistance {
+    /// Looks through the given candidates, the matchers comparing transformed copies
+    /// pass theirs.
+    pub(super) fn find(
         &self,
-        playlist_id: &mut String,
-        plex: &Plex,
-        plex_tracks: &Vec<TrackAlbumArtist>,
+        candidates: &[&TrackAlbumArtist],
         spotify_track: &TrackAlbumArtist,
-        playlist_name: &String,
-    ) -> Result<TrackAlbumArtist, anyhow::Error> {
-        let mut sorted: Vec<(usize, TrackAlbumArtist, String, String)> = plex_tracks
+        config: &MatchConfig,
+    ) -> Option<MatchResult> {
+        let spotify_artists: Vec<String> = spotify_track
+            .credited_artists()
+            .map(|a| config.artist_aliases.canonical(a))
+            .collect();
+
+        let mut sorted: Vec<(usize, &TrackAlbumArtist, String, String)> = candidates
             .iter()
+            .copied()
             .enumerate()
-            .map(|(_, f)| {
-                // get which one has the lowest distance.
-                let mut distances: Vec<(usize, String, String)> = f
-                    .artist
-                    .iter()
-                    .map(|artist| {
-                        spotify_track.artist.iter().map(|spotify_artist| {
-                            let spotify_artist = spotify_artist.clone();
-                            let artist = artist.clone();
+            // a different length means a different version of the song.
+            .filter(|(_, f)| within_tolerance(spotify_track, f, config))
+            .filter(|(_, f)| self.any_version || compatible(spotify_track, f))
+            .filter_map(|(_, f)| {
+                // get which one has the lowest distance, none without artists to compare.
+                let (artist_distance, spotify_artist, plex_artist) = f
+                    .credited_artists()
+                    .flat_map(|artist| {
+                        let artist = config.artist_aliases.canonical(artist);
+                        spotify_artists.iter().map(move |spotify_artist| {
                             (
-                                levenshtein(&spotify_artist, &artist),
+                                levenshtein(spotify_artist, &artist),
                                 spotify_artist.clone(),
                                 artist.clone(),
                             )
                         })
                     })
-                    .flatten()
-                    .collect();
-                distances.sort_by_key(|(d, _, _)| *d);
-                let (artist_distance, spotify_artist, plex_artist) = distances.get(0).unwrap();
+                    .min_by_key(|(d, _, _)| *d)?;
                 let track_distance = levenshtein(&spotify_track.track, &f.track);
-                return (
-                    *artist_distance + track_distance,
-                    f.clone(),
-                    spotify_artist.clone(),
-                    plex_artist.clone(),
-                );
+                // matching durations make up for a few differences in the names.
+                let distance = (artist_distance + track_distance)
+                    .saturating_sub(distance_allowance(spotify_track, f));
+                Some((distance, f, spotify_artist, plex_artist))
             })
             .collect();
 
         sorted.sort_by_key(|(distance, _, _, _)| *distance);
 
-        // get highest score?
-        if let Some((distance, plex_track, spotify_artist, plex_artist)) = sorted.get(0) {
-            if *distance <= 4 {
-                println!(
-                    "Closest match ({}): {} - {} => {} - {}",
-                    distance,
-                    spotify_artist,
-                    //spotify_track.album,
-                    spotify_track.track,
-                    plex_artist,
-                    //plex_track.album,
-                    plex_track.track
-                );
-                playlist(playlist_id, &plex, plex_track, playlist_name).await?;
-                return Ok(plex_track.clone());
-            }
-        };
+        let distance = sorted.first()?.0;
+        if distance > self.max_distance {
+            return None;
+        }
+        // several releases of the same song share the closest distance, prefer the closest release.
+        let closest: Vec<&TrackAlbumArtist> = sorted
+            .iter()
+            .take_while(|(d, _, _, _)| *d == distance)
+            .map(|(_, t, _, _)| *t)
+            .collect();
+        let plex_track = best_candidate(&closest, spotify_track, config)?;
+        // the artists compared for the track that was picked.
+        let (_, _, spotify_artist, plex_artist) = sorted
+            .iter()
+            .find(|(_, t, _, _)| std::ptr::eq(*t, plex_track))?;
+        info!(
+            "Closest match ({}): {} - {} => {} - {}",
+            distance, spotify_artist, spotify_track.track, plex_artist, plex_track.track
+        );
+        let explanation = MatchExplanation::new(
+            "levenshtein distance",
+            format!("{} - {}", spotify_artist, spotify_track.track),
+            format!("{} - {}", plex_artist, plex_track.track),
+            distance as f64,
+        );
+        Some(MatchResult::new(plex_track, explanation))
+    }
+}
+
+#[async_trait::async_trait]
+impl Matcher for LevenshteinDistance {
+    async fn match_fn(
+        &self,
+        plex_tracks: &PlexIndex,
+        spotify_track: &TrackAlbumArtist,
+        config: &MatchConfig,
+    ) -> Result<MatchResult, anyhow::Error> {
+        let candidates = plex_tracks.candidates(spotify_track, config);
+        self.find(&candidates, spotify_track, config)
+            .ok_or_else(|| anyhow::anyhow!("No match found"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(rating_key: &str, artist: &[&str], album: &str) -> TrackAlbumArtist {
+        TrackAlbumArtist {
+            track: "intro".to_string(),
+            artist: artist.iter().map(|a| a.to_string()).collect(),
+            album: album.to_string(),
+            ..TrackAlbumArtist::plex(rating_key)
+        }
+    }
+
+    #[test]
+    fn test_explanation() {
+        let config = MatchConfig::default();
+        let spotify_track = track("", &["the xx"], "xx");
+        // as close as each other, the one from the same album is picked.
+        let untitled = track("1", &[], "xx");
+        let other_album = track("2", &["xy"], "coexist");
+        let same_album = track("3", &["the x"], "xx");
+        let candidates = [&untitled, &other_album, &same_album];
+
+        let result = LevenshteinDistance::default()
+            .find(&candidates, &spotify_track, &config)
+            .unwrap();
+        assert_eq!(result.track.id(), "3");
+        // the artists are compared without "the".
+        assert_eq!(result.explanation.plex, "x - intro");
+        assert_eq!(result.explanation.score, 1.0);
 
-        Err(anyhow::anyhow!("No match found"))
+        // without artists to compare there is no match.
+        assert!(LevenshteinDistance::default()
+            .find(&[&untitled], &spotify_track, &config)
+            .is_none());
     }
 }
@@ -1,20 +1,112 @@
+pub mod album;
+pub mod artist_alias;
+pub mod assignment;
 pub mod character_replacement;
+pub mod duplicates;
+pub mod duration;
+pub mod explain;
 pub mod forward_backward;
+pub mod index;
+pub mod isrc;
 pub mod levenshtein;
+pub mod musicbrainz;
+pub mod pipeline;
+pub mod quality;
+pub mod ranking;
+pub mod registry;
 pub mod remove_sections;
+pub mod token_similarity;
+pub mod transliteration;
+pub mod version;
+
+use std::collections::HashMap;
+
+use album::ReleaseType;
+use artist_alias::ArtistAliases;
+use explain::MatchExplanation;
+use index::PlexIndex;
+use quality::QualityPreference;
 
 use crate::track_album_artist::TrackAlbumArtist;
 
-use super::client::Plex;
+pub struct MatchConfig {
+    /// Order in which releases are preferred when several albums contain the same track.
+    pub release_preference: Vec<ReleaseType>,
+    /// Candidates whose duration differs by more than this are rejected, `None` disables the check.
+    pub duration_tolerance_ms: Option<u64>,
+    /// Romanizations of whole names, for readings the built in tables can't know.
+    pub transliterations: HashMap<String, String>,
+    /// Artists known under several names, consulted whenever artists are compared.
+    pub artist_aliases: ArtistAliases,
+    /// How to choose between copies of a track that match equally well.
+    pub quality_preference: Vec<QualityPreference>,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        Self {
+            release_preference: vec![
+                ReleaseType::Studio,
+                ReleaseType::Compilation,
+                ReleaseType::Live,
+            ],
+            duration_tolerance_ms: Some(10_000),
+            transliterations: HashMap::new(),
+            artist_aliases: ArtistAliases::default(),
+            quality_preference: vec![
+                QualityPreference::Release,
+                QualityPreference::Lossless,
+                QualityPreference::Bitrate,
+            ],
+        }
+    }
+}
+
+/// A plex track found for a spotify track.
+#[derive(Clone)]
+pub struct MatchResult {
+    pub track: TrackAlbumArtist,
+    pub explanation: MatchExplanation,
+}
+
+impl MatchResult {
+    pub fn new(track: &TrackAlbumArtist, explanation: MatchExplanation) -> Self {
+        Self {
+            track: track.clone(),
+            explanation,
+        }
+    }
+
+    /// The same match with the candidate it was made from, for matchers comparing
+    /// transformed copies of the candidates, so that the original names are reported.
+    pub fn original(mut self, candidates: &[&TrackAlbumArtist]) -> Self {
+        if let Some(original) = candidates.iter().find(|c| c.id() == self.track.id()) {
+            self.track = (*original).clone();
+        }
+        self
+    }
+
+    /// Records a transformation made before the one of the matcher that found it.
+    /// Flags a match to another version of the song than the spotify one.
+    pub fn check_version(mut self, spotify_track: &TrackAlbumArtist) -> Self {
+        self.explanation.other_version = !version::compatible(spotify_track, &self.track);
+        self
+    }
+
+    pub fn after(mut self, transformation: impl Into<String>) -> Self {
+        self.explanation
+            .transformations
+            .insert(0, transformation.into());
+        self
+    }
+}
 
 #[async_trait::async_trait]
 pub trait Matcher: Send + Sync {
     async fn match_fn(
         &self,
-        playlist_id: &mut String,
-        plex: &Plex,
-        plex_tracks: &Vec<TrackAlbumArtist>,
+        plex_tracks: &PlexIndex,
         spotify_track: &TrackAlbumArtist,
-        playlist_name: &String,
-    ) -> Result<TrackAlbumArtist, anyhow::Error>;
+        config: &MatchConfig,
+    ) -> Result<MatchResult, anyhow::Error>;
 }
@@ -0,0 +1,137 @@
+use std::sync::Arc;
+
+use crate::{musicbrainz::MusicBrainzLookup, track_album_artist::TrackAlbumArtist};
+
+use super::{
+    explain::MatchExplanation, index::PlexIndex, ranking::best_candidate, MatchConfig, MatchResult,
+    Matcher,
+};
+
+/// Exact match for libraries tagged with MusicBrainz ids, the spotify track is resolved
+/// to its recordings through the ISRC.
+pub struct MatchMusicBrainz {
+    pub lookup: Arc<dyn MusicBrainzLookup>,
+}
+#[async_trait::async_trait]
+impl Matcher for MatchMusicBrainz {
+    async fn match_fn(
+        &self,
+        plex_tracks: &PlexIndex,
+        spotify_track: &TrackAlbumArtist,
+        config: &MatchConfig,
+    ) -> Result<MatchResult, anyhow::Error> {
+        let Some(isrc) = &spotify_track.isrc else {
+            return Err(anyhow::anyhow!("No ISRC for spotify track"));
+        };
+        // nothing to find, without a lookup that is rate limited.
+        if !plex_tracks.has_musicbrainz_ids() {
+            return Err(anyhow::anyhow!("No MusicBrainz ids in the plex library"));
+        }
+
+        let recordings = self.lookup.recordings_for_isrc(isrc).await?;
+        let candidates = plex_tracks.by_musicbrainz_ids(&recordings);
+
+        if let Some(plex_track) = best_candidate(&candidates, spotify_track, config) {
+            let explanation = MatchExplanation::new(
+                "musicbrainz recording",
+                format!("{} ({})", isrc, recordings.join(", ")),
+                plex_track.musicbrainz_ids.join(", "),
+                1.0,
+            );
+            return Ok(MatchResult::new(plex_track, explanation));
+        }
+        Err(anyhow::anyhow!("No match found"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+
+    /// Answers from a fixed table instead of a dump or the web service.
+    struct FakeLookup {
+        recordings: HashMap<String, Vec<String>>,
+        lookups: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl MusicBrainzLookup for FakeLookup {
+        async fn recordings_for_isrc(&self, isrc: &str) -> Result<Vec<String>, anyhow::Error> {
+            self.lookups.fetch_add(1, Ordering::Relaxed);
+            match isrc {
+                "UNAVAILABLE" => Err(anyhow::anyhow!("service unavailable")),
+                isrc => Ok(self.recordings.get(isrc).cloned().unwrap_or_default()),
+            }
+        }
+    }
+
+    fn track(rating_key: &str, isrc: Option<&str>, mbids: &[&str]) -> TrackAlbumArtist {
+        TrackAlbumArtist {
+            track: "hurt".to_string(),
+            artist: vec!["johnny cash".to_string()],
+            isrc: isrc.map(|i| i.to_string()),
+            musicbrainz_ids: mbids.iter().map(|m| m.to_string()).collect(),
+            ..TrackAlbumArtist::plex(rating_key)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_match_musicbrainz() {
+        let config = MatchConfig::default();
+        let plex_tracks = PlexIndex::new(
+            vec![
+                track("1", None, &["f1c0a5a0"]),
+                track("2", None, &["9d3b6e1c", "0b7e3a42"]),
+            ],
+            &config,
+        );
+        let lookup = Arc::new(FakeLookup {
+            recordings: HashMap::from([
+                ("USUM70200839".to_string(), vec!["0b7e3a42".to_string()]),
+                ("GBAYE0000001".to_string(), vec!["4e5d0c11".to_string()]),
+            ]),
+            lookups: AtomicUsize::new(0),
+        });
+        let matcher = MatchMusicBrainz {
+            lookup: lookup.clone(),
+        };
+
+        let result = matcher
+            .match_fn(&plex_tracks, &track("", Some("USUM70200839"), &[]), &config)
+            .await
+            .unwrap();
+        assert_eq!(result.track.id(), "2");
+
+        // without an isrc, with a recording that isn't in the library, unknown to
+        // musicbrainz or when the lookup fails.
+        for isrc in [
+            None,
+            Some("GBAYE0000001"),
+            Some("USRC10000001"),
+            Some("UNAVAILABLE"),
+        ] {
+            assert!(
+                matcher
+                    .match_fn(&plex_tracks, &track("", isrc, &[]), &config)
+                    .await
+                    .is_err(),
+                "{:?}",
+                isrc
+            );
+        }
+
+        // a library without musicbrainz ids isn't looked up at all.
+        let lookups = lookup.lookups.load(Ordering::Relaxed);
+        let untagged = PlexIndex::new(vec![track("3", None, &[])], &config);
+        assert!(matcher
+            .match_fn(&untagged, &track("", Some("USUM70200839"), &[]), &config)
+            .await
+            .is_err());
+        assert_eq!(lookup.lookups.load(Ordering::Relaxed), lookups);
+    }
+}
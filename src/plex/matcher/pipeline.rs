@@ -0,0 +1,39 @@
+use crate::track_album_artist::TrackAlbumArtist;
+
+use super::{index::PlexIndex, MatchConfig, MatchResult, Matcher};
+
+/// Matchers tried in order until one of them finds the track.
+pub struct Pipeline {
+    matchers: Vec<(String, Box<dyn Matcher>)>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self { matchers: vec![] }
+    }
+
+    pub fn push(&mut self, name: &str, matcher: Box<dyn Matcher>) {
+        self.matchers.push((name.to_string(), matcher));
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.matchers.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// The first match, explained with the name of the matcher that found it.
+    pub async fn run(
+        &self,
+        plex_tracks: &PlexIndex,
+        spotify_track: &TrackAlbumArtist,
+        config: &MatchConfig,
+    ) -> Option<MatchResult> {
+        for (name, matcher) in self.matchers.iter() {
+            let result = matcher.match_fn(plex_tracks, spotify_track, config).await;
+            if let Ok(mut result) = result {
+                result.explanation.matcher = name.clone();
+                return Some(result);
+            }
+        }
+        None
+    }
+}
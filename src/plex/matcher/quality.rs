@@ -0,0 +1,142 @@
+use clap::ValueEnum;
+
+use crate::track_album_artist::{MediaInfo, MetadataType, TrackAlbumArtist};
+
+use super::{
+    album::{release_rank, release_type},
+    MatchConfig,
+};
+
+/// Codecs plex reports for lossless files.
+const LOSSLESS_CODECS: [&str; 7] = ["flac", "alac", "pcm", "wav", "aiff", "ape", "wavpack"];
+
+/// What makes a copy better than another one of the same track, in order of importance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum QualityPreference {
+    /// The release preference, e.g. the studio album over a compilation.
+    Release,
+    /// Lossless files over lossy ones, e.g. flac over mp3.
+    Lossless,
+    /// The highest bitrate.
+    Bitrate,
+    /// The most audio channels.
+    Channels,
+}
+
+fn media(track: &TrackAlbumArtist) -> &[MediaInfo] {
+    match &track.metadata {
+        MetadataType::Plex(meta) => &meta.media,
+        MetadataType::Spotify(_) => &[],
+    }
+}
+
+fn is_lossless(media: &MediaInfo) -> bool {
+    media
+        .codec
+        .as_ref()
+        .is_some_and(|c| LOSSLESS_CODECS.contains(&c.as_str()))
+}
+
+/// How the track does on each of the configured preferences, higher is better. Tracks
+/// with several files count their best one. The release preference still breaks the
+/// remaining ties when it isn't listed.
+pub fn quality_key(track: &TrackAlbumArtist, config: &MatchConfig) -> Vec<u64> {
+    let media = media(track);
+    let mut preferences = config.quality_preference.clone();
+    if !preferences.contains(&QualityPreference::Release) {
+        preferences.push(QualityPreference::Release);
+    }
+    preferences
+        .iter()
+        .map(|preference| match preference {
+            QualityPreference::Release => {
+                let rank = release_rank(release_type(&track.album), config);
+                (config.release_preference.len() - rank) as u64
+            }
+            QualityPreference::Lossless => media.iter().any(is_lossless) as u64,
+            QualityPreference::Bitrate => media
+                .iter()
+                .filter_map(|m| m.bitrate)
+                .max()
+                .unwrap_or(0)
+                .into(),
+            QualityPreference::Channels => media
+                .iter()
+                .filter_map(|m| m.channels)
+                .max()
+                .unwrap_or(0)
+                .into(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{plex::matcher::ranking::best_candidate, track_album_artist::PlexMetadata};
+
+    fn track(rating_key: &str, album: &str, codec: &str, bitrate: u32) -> TrackAlbumArtist {
+        TrackAlbumArtist {
+            track: "hurt".to_string(),
+            album: album.to_string(),
+            artist: vec!["johnny cash".to_string()],
+            duration_ms: Some(218_000),
+            metadata: MetadataType::Plex(PlexMetadata {
+                rating_key: rating_key.to_string(),
+                media: vec![MediaInfo {
+                    codec: Some(codec.to_string()),
+                    bitrate: Some(bitrate),
+                    channels: Some(2),
+                    file: None,
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_quality_preference() {
+        let spotify_track = track("", "american iv: the man comes around", "", 0);
+        let candidates = [
+            track("1", "the essential johnny cash", "mp3", 320),
+            track("2", "american iv: the man comes around", "mp3", 192),
+            track("3", "american iv: the man comes around", "flac", 900),
+            track("4", "american iv: the man comes around", "mp3", 320),
+        ];
+        let candidates: Vec<&TrackAlbumArtist> = candidates.iter().collect();
+
+        let cases = [
+            (vec![QualityPreference::Lossless], "3"),
+            (vec![QualityPreference::Bitrate], "3"),
+            (vec![QualityPreference::Release], "2"),
+            (vec![], "2"),
+        ];
+        for (quality_preference, expected) in cases {
+            let config = MatchConfig {
+                quality_preference,
+                ..Default::default()
+            };
+            let best = best_candidate(&candidates, &spotify_track, &config).unwrap();
+            assert_eq!(best.id(), expected, "{:?}", config.quality_preference);
+        }
+
+        // copies as good as each other, the release preference decides even when it
+        // isn't listed.
+        let spotify_track = track("", "", "", 0);
+        let copies = [candidates[0], candidates[3]];
+        for quality_preference in [vec![QualityPreference::Lossless], vec![]] {
+            let config = MatchConfig {
+                quality_preference,
+                ..Default::default()
+            };
+            let best = best_candidate(&copies, &spotify_track, &config).unwrap();
+            assert_eq!(best.id(), "4", "{:?}", config.quality_preference);
+        }
+
+        // the album from spotify comes first, whatever its quality.
+        let spotify_track = track("", "the essential johnny cash", "", 0);
+        let best = best_candidate(&candidates, &spotify_track, &MatchConfig::default()).unwrap();
+        assert_eq!(best.id(), "1");
+    }
+}
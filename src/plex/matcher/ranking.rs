@@ -0,0 +1,167 @@
+use crate::track_album_artist::TrackAlbumArtist;
+
+use super::{
+    album::album_similarity, duration::duration_similarity, quality::quality_key,
+    version::version_similarity, MatchConfig,
+};
+
+/// A close duration says more about the recording than a close album title.
+const DURATION_WEIGHT: f64 = 2.0;
+/// A different version is penalized more than anything else.
+const VERSION_WEIGHT: f64 = 3.0;
+/// Scores this close are the same release, e.g. a flac and an mp3 rip a few hundred
+/// milliseconds apart (half a second at the default duration tolerance).
+const SCORE_TOLERANCE: f64 = 0.1;
+
+/// How close the release is to spotify's, from 0.0 to 6.0.
+pub(super) fn score(
+    plex_track: &TrackAlbumArtist,
+    spotify_track: &TrackAlbumArtist,
+    config: &MatchConfig,
+) -> f64 {
+    album_similarity(&spotify_track.album, &plex_track.album)
+        + DURATION_WEIGHT * duration_similarity(spotify_track, plex_track, config)
+        + VERSION_WEIGHT * version_similarity(spotify_track, plex_track)
+}
+
+/// How well the position on the album agrees, only meaningful when both tracks are
+/// from the same album, e.g. to tell apart the "intro" of each disc of a deluxe edition.
+fn position_score(plex_track: &TrackAlbumArtist, spotify_track: &TrackAlbumArtist) -> u8 {
+    if plex_track.album != spotify_track.album
+        || plex_track.track_number.is_none()
+        || plex_track.track_number != spotify_track.track_number
+    {
+        return 0;
+    }
+    match (plex_track.disc_number, spotify_track.disc_number) {
+        (Some(a), Some(b)) if a == b => 2,
+        (Some(_), Some(_)) => 0,
+        _ => 1,
+    }
+}
+
+/// Pick the candidate that is most likely the exact release from spotify. Candidates
+/// whose scores are within the tolerance of the best one are told apart by their
+/// position on the album, then by the configured quality preference, then by score.
+pub fn best_candidate<'a>(
+    candidates: &[&'a TrackAlbumArtist],
+    spotify_track: &TrackAlbumArtist,
+    config: &MatchConfig,
+) -> Option<&'a TrackAlbumArtist> {
+    let scored: Vec<(f64, &TrackAlbumArtist)> = candidates
+        .iter()
+        .map(|c| (score(c, spotify_track, config), *c))
+        .collect();
+    let best = scored.iter().map(|(s, _)| *s).fold(f64::MIN, f64::max);
+    scored
+        .into_iter()
+        .filter(|(s, _)| best - s <= SCORE_TOLERANCE)
+        .map(|(s, c)| {
+            (
+                position_score(c, spotify_track),
+                quality_key(c, config),
+                s,
+                c,
+            )
+        })
+        // max_by returns the last of equal elements, so walk backwards to keep the first.
+        .rev()
+        .max_by(
+            |(a_pos, a_quality, a_score, _), (b_pos, b_quality, b_score, _)| {
+                a_pos
+                    .cmp(b_pos)
+                    .then(a_quality.cmp(b_quality))
+                    .then(a_score.total_cmp(b_score))
+            },
+        )
+        .map(|(_, _, _, c)| c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        plex::matcher::quality::QualityPreference,
+        track_album_artist::{MediaInfo, MetadataType, PlexMetadata},
+    };
+
+    fn track(
+        rating_key: &str,
+        album: &str,
+        track_number: Option<u32>,
+        disc_number: Option<u32>,
+    ) -> TrackAlbumArtist {
+        TrackAlbumArtist {
+            track: "intro".to_string(),
+            album: album.to_string(),
+            artist: vec!["the xx".to_string()],
+            duration_ms: Some(128_000),
+            track_number,
+            disc_number,
+            ..TrackAlbumArtist::plex(rating_key)
+        }
+    }
+
+    #[test]
+    fn test_position() {
+        let config = MatchConfig::default();
+        // the "intro" of each disc of a deluxe edition.
+        let disc_1 = track("1", "xx (deluxe)", Some(1), Some(1));
+        let disc_2 = track("2", "xx (deluxe)", Some(1), Some(2));
+        let elsewhere = track("3", "xx (deluxe)", Some(4), Some(2));
+        let candidates = [&disc_1, &disc_2, &elsewhere];
+
+        let cases = [
+            (Some(1), Some(2), "2"),
+            (Some(1), Some(1), "1"),
+            // without a disc number, the first one with the same track number.
+            (Some(1), None, "1"),
+            (Some(4), Some(2), "3"),
+            // no position to go by, the first candidate.
+            (None, None, "1"),
+        ];
+        for (track_number, disc_number, expected) in cases {
+            let spotify_track = track("", "xx (deluxe)", track_number, disc_number);
+            let best = best_candidate(&candidates, &spotify_track, &config).unwrap();
+            assert_eq!(best.id(), expected, "{:?} {:?}", track_number, disc_number);
+        }
+
+        // the position only counts on the same album.
+        let spotify_track = track("", "xx", Some(1), Some(2));
+        let other_album = track("4", "xx", None, None);
+        let best = best_candidate(&[&disc_2, &other_album], &spotify_track, &config).unwrap();
+        assert_eq!(best.id(), "4");
+    }
+
+    #[test]
+    fn test_score_tolerance() {
+        let config = MatchConfig {
+            quality_preference: vec![QualityPreference::Lossless],
+            ..MatchConfig::default()
+        };
+        let copy = |rating_key: &str, codec: &str, duration_ms: u64| TrackAlbumArtist {
+            duration_ms: Some(duration_ms),
+            metadata: MetadataType::Plex(PlexMetadata {
+                rating_key: rating_key.to_string(),
+                media: vec![MediaInfo {
+                    codec: Some(codec.to_string()),
+                    ..MediaInfo::default()
+                }],
+                ..PlexMetadata::default()
+            }),
+            ..track(rating_key, "xx", None, None)
+        };
+        let spotify_track = track("", "xx", None, None);
+
+        // the mp3 is a few milliseconds closer, the flac is still the same release.
+        let mp3 = copy("1", "mp3", 128_010);
+        let flac = copy("2", "flac", 128_240);
+        let best = best_candidate(&[&mp3, &flac], &spotify_track, &config).unwrap();
+        assert_eq!(best.id(), "2");
+
+        // seconds apart is another recording, whatever its quality.
+        let flac = copy("2", "flac", 131_000);
+        let best = best_candidate(&[&mp3, &flac], &spotify_track, &config).unwrap();
+        assert_eq!(best.id(), "1");
+    }
+}
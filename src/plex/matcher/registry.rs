@@ -0,0 +1,304 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Context;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Map, Value};
+
+use crate::musicbrainz::{MusicBrainzApi, MusicBrainzDump, MusicBrainzLookup};
+
+use super::{
+    character_replacement::MatchWithCharReplacements, forward_backward::MatchForwardBack,
+    isrc::MatchIsrc, levenshtein::LevenshteinDistance, musicbrainz::MatchMusicBrainz,
+    pipeline::Pipeline, remove_sections::RemoveSections, token_similarity::MatchTokenSimilarity,
+    transliteration::MatchTransliterated, Matcher,
+};
+
+/// One step of the pipeline as declared in the config, e.g.
+/// `{"name": "levenshtein", "max_distance": 3}`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct MatcherSpec {
+    pub name: String,
+    #[serde(flatten)]
+    pub params: Map<String, Value>,
+}
+
+impl MatcherSpec {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            params: Map::new(),
+        }
+    }
+}
+
+/// Builds a matcher from its parameters, given the whole pipeline for the matchers
+/// that run other configured matchers.
+pub type MatcherFactory = Box<
+    dyn Fn(&Map<String, Value>, &[MatcherSpec]) -> Result<Box<dyn Matcher>, anyhow::Error>
+        + Send
+        + Sync,
+>;
+
+/// Builds matchers from their name and parameters.
+pub struct MatcherRegistry {
+    factories: HashMap<String, MatcherFactory>,
+}
+
+/// Matchers whose parameters are their fields.
+fn from_params<T: Matcher + DeserializeOwned + 'static>(
+    params: &Map<String, Value>,
+    _specs: &[MatcherSpec],
+) -> Result<Box<dyn Matcher>, anyhow::Error> {
+    let matcher: T = serde_json::from_value(Value::Object(params.clone()))?;
+    Ok(Box::new(matcher))
+}
+
+/// Matchers that take no parameters.
+fn without_params(
+    matcher: impl Fn(&[MatcherSpec]) -> Result<Box<dyn Matcher>, anyhow::Error> + Send + Sync + 'static,
+) -> MatcherFactory {
+    Box::new(move |params, specs| match params.keys().next() {
+        Some(key) => Err(anyhow::anyhow!("unknown parameter `{}`", key)),
+        None => matcher(specs),
+    })
+}
+
+/// The matcher as configured in the pipeline, with its defaults when the pipeline
+/// doesn't have it.
+fn configured<T: DeserializeOwned + Default>(
+    specs: &[MatcherSpec],
+    name: &str,
+) -> Result<T, anyhow::Error> {
+    match specs.iter().find(|spec| spec.name == name) {
+        Some(spec) => Ok(serde_json::from_value(Value::Object(spec.params.clone()))?),
+        None => Ok(T::default()),
+    }
+}
+
+fn char_replacements(
+    params: &Map<String, Value>,
+    specs: &[MatcherSpec],
+) -> Result<MatchWithCharReplacements, anyhow::Error> {
+    let mut matcher: MatchWithCharReplacements =
+        serde_json::from_value(Value::Object(params.clone()))?;
+    matcher.forward_backward = configured(specs, "forward_backward")?;
+    matcher.levenshtein = configured(specs, "levenshtein")?;
+    Ok(matcher)
+}
+
+fn remove_sections(specs: &[MatcherSpec]) -> Result<RemoveSections, anyhow::Error> {
+    let params = specs
+        .iter()
+        .find(|spec| spec.name == "char_replacements")
+        .map(|spec| spec.params.clone())
+        .unwrap_or_default();
+    let mut matcher = RemoveSections {
+        forward_backward: configured(specs, "forward_backward")?,
+        levenshtein: configured(specs, "levenshtein")?,
+        char_replacements: char_replacements(&params, specs)?,
+    };
+    matcher.forward_backward.any_version = true;
+    matcher.levenshtein.any_version = true;
+    matcher.char_replacements.forward_backward.any_version = true;
+    matcher.char_replacements.levenshtein.any_version = true;
+    Ok(matcher)
+}
+
+fn transliteration(specs: &[MatcherSpec]) -> Result<MatchTransliterated, anyhow::Error> {
+    Ok(MatchTransliterated {
+        forward_backward: configured(specs, "forward_backward")?,
+        levenshtein: configured(specs, "levenshtein")?,
+    })
+}
+
+fn musicbrainz(
+    params: &Map<String, Value>,
+    _specs: &[MatcherSpec],
+) -> Result<Box<dyn Matcher>, anyhow::Error> {
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Params {
+        dump: Option<String>,
+        #[serde(default)]
+        api: bool,
+    }
+    let params: Params = serde_json::from_value(Value::Object(params.clone()))?;
+    let lookup: Arc<dyn MusicBrainzLookup> = match params.dump {
+        Some(path) => Arc::new(MusicBrainzDump::load(&path)?),
+        None if params.api => Arc::new(MusicBrainzApi::new()?),
+        None => return Err(anyhow::anyhow!("either `dump` or `api` is required")),
+    };
+    Ok(Box::new(MatchMusicBrainz { lookup }))
+}
+
+impl MatcherRegistry {
+    pub fn new() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, name: &str, factory: MatcherFactory) {
+        self.factories.insert(name.to_string(), factory);
+    }
+
+    pub fn build(&self, specs: &[MatcherSpec]) -> Result<Pipeline, anyhow::Error> {
+        let mut pipeline = Pipeline::new();
+        for spec in specs {
+            let factory = self.factories.get(&spec.name).ok_or_else(|| {
+                let mut known: Vec<&String> = self.factories.keys().collect();
+                known.sort();
+                anyhow::anyhow!(
+                    "unknown matcher `{}`, expected one of {:?}",
+                    spec.name,
+                    known
+                )
+            })?;
+            let matcher = factory(&spec.params, specs)
+                .with_context(|| format!("invalid parameters for matcher `{}`", spec.name))?;
+            pipeline.push(&spec.name, matcher);
+        }
+        Ok(pipeline)
+    }
+}
+
+impl Default for MatcherRegistry {
+    /// The built in matchers.
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register("isrc", without_params(|_| Ok(Box::new(MatchIsrc {}))));
+        registry.register("musicbrainz", Box::new(musicbrainz));
+        registry.register(
+            "forward_backward",
+            Box::new(from_params::<MatchForwardBack>),
+        );
+        registry.register("levenshtein", Box::new(from_params::<LevenshteinDistance>));
+        registry.register(
+            "token_similarity",
+            Box::new(from_params::<MatchTokenSimilarity>),
+        );
+        registry.register(
+            "char_replacements",
+            Box::new(|params, specs| Ok(Box::new(char_replacements(params, specs)?))),
+        );
+        registry.register(
+            "remove_sections",
+            without_params(|specs| Ok(Box::new(remove_sections(specs)?))),
+        );
+        registry.register(
+            "transliteration",
+            without_params(|specs| Ok(Box::new(transliteration(specs)?))),
+        );
+        registry
+    }
+}
+
+/// The pipeline used when the config doesn't declare one, the musicbrainz matcher
+/// runs right after the ISRC one when given.
+pub fn default_matchers(musicbrainz: Option<MatcherSpec>) -> Vec<MatcherSpec> {
+    let mut specs = vec![MatcherSpec::new("isrc")];
+    specs.extend(musicbrainz);
+    for name in [
+        "forward_backward",
+        "levenshtein",
+        "token_similarity",
+        "char_replacements",
+        "remove_sections",
+        "transliteration",
+    ] {
+        specs.push(MatcherSpec::new(name));
+    }
+    specs
+}
+
+/// The musicbrainz step for the `--musicbrainz-dump` and `--musicbrainz-api` options.
+pub fn musicbrainz_matcher(dump: Option<String>, api: bool) -> Option<MatcherSpec> {
+    let params = match dump {
+        Some(path) => json!({ "dump": path }),
+        None if api => json!({ "api": true }),
+        None => return None,
+    };
+    let Value::Object(params) = params else {
+        unreachable!()
+    };
+    Some(MatcherSpec {
+        name: "musicbrainz".to_string(),
+        params,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn specs(config: Value) -> Vec<MatcherSpec> {
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn test_build() {
+        let registry = MatcherRegistry::default();
+        let pipeline = registry
+            .build(&specs(json!([
+                {"name": "isrc"},
+                {"name": "levenshtein", "max_distance": 2},
+                {"name": "char_replacements", "replacements": {"+": "and"}},
+            ])))
+            .unwrap();
+        assert_eq!(
+            pipeline.names().collect::<Vec<&str>>(),
+            vec!["isrc", "levenshtein", "char_replacements"]
+        );
+        assert!(registry.build(&default_matchers(None)).is_ok());
+
+        let cases = [
+            json!([{"name": "fuzzy"}]),
+            json!([{"name": "levenshtein", "max_distance": "two"}]),
+            json!([{"name": "levenshtein", "threshold": 2}]),
+            json!([{"name": "isrc", "any_version": true}]),
+            json!([{"name": "musicbrainz"}]),
+        ];
+        for config in cases {
+            assert!(
+                registry.build(&specs(config.clone())).is_err(),
+                "{}",
+                config
+            );
+        }
+    }
+
+    #[test]
+    fn test_nested_matchers() {
+        let config = specs(json!([
+            {"name": "levenshtein", "max_distance": 1},
+            {"name": "char_replacements", "replacements": {"+": "and"}},
+        ]));
+        let matcher = remove_sections(&config).unwrap();
+        assert!(matcher.forward_backward.any_version);
+        assert!(matcher.levenshtein.any_version);
+        assert_eq!(matcher.levenshtein.max_distance, 1);
+        assert_eq!(matcher.char_replacements.replacements["+"], "and");
+        assert_eq!(matcher.char_replacements.levenshtein.max_distance, 1);
+        assert!(matcher.char_replacements.levenshtein.any_version);
+
+        // the replacements keep the order of the config file.
+        let ordered: Vec<MatcherSpec> = serde_json::from_str(
+            r#"[{"name": "char_replacements", "replacements": {"+": " and ", "&": "+"}}]"#,
+        )
+        .unwrap();
+        let matcher = remove_sections(&ordered).unwrap();
+        assert_eq!(
+            matcher
+                .char_replacements
+                .replacements
+                .keys()
+                .collect::<Vec<&String>>(),
+            vec!["+", "&"]
+        );
+
+        let matcher = transliteration(&config).unwrap();
+        assert_eq!(matcher.levenshtein.max_distance, 1);
+        let matcher = transliteration(&[]).unwrap();
+        assert_eq!(matcher.levenshtein.max_distance, 4);
+    }
+}
@@ -1,90 +1,150 @@
-use crate::{plex::client::Plex, track_album_artist::TrackAlbumArtist};
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::track_album_artist::TrackAlbumArtist;
 
 use super::{
     character_replacement::MatchWithCharReplacements, forward_backward::MatchForwardBack,
-    levenshtein::LevenshteinDistance, Matcher,
+    index::PlexIndex, levenshtein::LevenshteinDistance, MatchConfig, MatchResult, Matcher,
 };
 
-pub struct RemoveSections {}
+/// The sections removed one after the other and how they are explained, featured
+/// artists and versions are already split off the titles.
+static SECTIONS: LazyLock<[(Regex, &str); 3]> = LazyLock::new(|| {
+    [
+        (Regex::new(r"\(.+?\)").unwrap(), "(...)"),
+        (Regex::new(r"\[.+?\]").unwrap(), "[...]"),
+        (Regex::new(r" - .+$").unwrap(), "\" - ...\""),
+    ]
+});
+
+/// The name with every section removed, the last form this matcher compares.
+pub(super) fn without_sections(s: &str) -> String {
+    SECTIONS
+        .iter()
+        .fold(s.to_string(), |s, (r, _)| r.replace_all(&s, "").to_string())
+}
+
+/// Last resort that compares titles without anything in brackets and without their
+/// version, so e.g. the live version is used when the library only has that one.
+/// Candidates of the same version still rank first, matches to another version are
+/// flagged to be checked.
+#[derive(Default)]
+pub struct RemoveSections {
+    /// The configured matchers compared without the sections, the first two for
+    /// any version.
+    pub forward_backward: MatchForwardBack,
+    pub levenshtein: LevenshteinDistance,
+    pub char_replacements: MatchWithCharReplacements,
+}
 #[async_trait::async_trait]
 impl Matcher for RemoveSections {
     async fn match_fn(
         &self,
-        playlist_id: &mut String,
-        plex: &Plex,
-        plex_tracks: &Vec<TrackAlbumArtist>,
+        plex_tracks: &PlexIndex,
         spotify_track: &TrackAlbumArtist,
-        playlist_name: &String,
-    ) -> Result<TrackAlbumArtist, anyhow::Error> {
-        let re = vec![
-            regex::Regex::new(r"\(feat.+?\)").unwrap(),
-            regex::Regex::new(r"\(.+?remix\)").unwrap(),
-            regex::Regex::new(r"\(.+?version\)").unwrap(),
-            regex::Regex::new(r"\(.+?radio edit\)").unwrap(),
-            regex::Regex::new(r"\(.+?remastered\)").unwrap(),
-            regex::Regex::new(r"\(.+?mix\)").unwrap(),
-            regex::Regex::new(r"\(.+?\)").unwrap(),
-        ];
-
+        config: &MatchConfig,
+    ) -> Result<MatchResult, anyhow::Error> {
+        let candidates = plex_tracks.candidates(spotify_track, config);
         let mut spotify_track = spotify_track.clone();
-        for r in re.iter() {
-            for artist in spotify_track.artist.iter_mut() {
-                *artist = r.replace_all(&artist, "").to_string();
+        let mut removed = vec![];
+        for (r, section) in SECTIONS.iter() {
+            removed.push(format!("removed {}", section));
+            for artist in spotify_track
+                .artist
+                .iter_mut()
+                .chain(spotify_track.featured_artist.iter_mut())
+            {
+                *artist = r.replace_all(artist, "").to_string();
             }
 
             spotify_track.track = r.replace_all(&spotify_track.track, "").to_string();
 
-            let new_plex_tracks: Vec<TrackAlbumArtist> = plex_tracks
+            let new_plex_tracks: Vec<TrackAlbumArtist> = candidates
                 .iter()
                 .map(|p| {
-                    let mut track = p.clone();
-                    for r in re.iter() {
-                        for artist in track.artist.iter_mut() {
-                            *artist = r.replace_all(&artist, "").to_string();
-                        }
+                    let mut track = (*p).clone();
+                    for artist in track
+                        .artist
+                        .iter_mut()
+                        .chain(track.featured_artist.iter_mut())
+                    {
+                        *artist = without_sections(artist);
                     }
                     track.track = r.replace_all(&track.track, "").to_string();
                     track
                 })
                 .collect();
+            let new_plex_tracks: Vec<&TrackAlbumArtist> = new_plex_tracks.iter().collect();
 
-            let result = MatchForwardBack {}
-                .match_fn(
-                    playlist_id,
-                    plex,
-                    &new_plex_tracks,
-                    &spotify_track,
-                    playlist_name,
-                )
-                .await;
-            if result.is_ok() {
-                return result;
-            }
-            let result = LevenshteinDistance {}
-                .match_fn(
-                    playlist_id,
-                    plex,
-                    &new_plex_tracks,
-                    &spotify_track,
-                    playlist_name,
-                )
-                .await;
-            if result.is_ok() {
-                return result;
-            }
-            let result = MatchWithCharReplacements {}
-                .match_fn(
-                    playlist_id,
-                    plex,
-                    &new_plex_tracks,
-                    &spotify_track,
-                    playlist_name,
-                )
-                .await;
-            if result.is_ok() {
-                return result;
+            let result = self
+                .forward_backward
+                .find(&new_plex_tracks, &spotify_track, config)
+                .or_else(|| {
+                    self.levenshtein
+                        .find(&new_plex_tracks, &spotify_track, config)
+                })
+                .or_else(|| {
+                    self.char_replacements
+                        .find(&new_plex_tracks, &spotify_track, config)
+                });
+            if let Some(result) = result {
+                // only the names were changed, the versions are still the parsed ones.
+                return Ok(result
+                    .original(&candidates)
+                    .after(removed.join(", "))
+                    .check_version(&spotify_track));
             }
         }
         Err(anyhow::anyhow!("No match found"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::track_version::{TrackVersion, VersionKind};
+
+    fn track(rating_key: &str, kind: Option<VersionKind>) -> TrackAlbumArtist {
+        TrackAlbumArtist {
+            track: "song".to_string(),
+            artist: vec!["band".to_string()],
+            version: kind.map(|kind| TrackVersion {
+                kind,
+                qualifier: None,
+            }),
+            ..TrackAlbumArtist::plex(rating_key)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_other_version() {
+        let config = MatchConfig::default();
+        let mut matcher = RemoveSections::default();
+        matcher.forward_backward.any_version = true;
+        matcher.levenshtein.any_version = true;
+        let spotify_track = track("", Some(VersionKind::Acoustic));
+
+        let cases = [
+            (Some(VersionKind::Remix), true),
+            (Some(VersionKind::Acoustic), false),
+        ];
+        for (kind, other_version) in cases {
+            let plex_tracks = PlexIndex::new(vec![track("1", kind)], &config);
+            let result = matcher
+                .match_fn(&plex_tracks, &spotify_track, &config)
+                .await
+                .unwrap();
+            assert_eq!(
+                result.explanation.other_version, other_version,
+                "{:?}",
+                kind
+            );
+            assert_eq!(
+                result.explanation.to_string().ends_with(", other version"),
+                other_version
+            );
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::BTreeSet;
+
+use levenshtein::levenshtein;
+use serde::Deserialize;
+
+use crate::{normalize::comparison_key, track_album_artist::TrackAlbumArtist};
+
+use super::{
+    duration::within_tolerance, explain::MatchExplanation, index::PlexIndex,
+    ranking::best_candidate, version::compatible, MatchConfig, MatchResult, Matcher,
+};
+
+/// Candidates scoring within this of the best one are considered equally good.
+const SCORE_EPSILON: f64 = 0.01;
+
+/// Edit distance relative to the length of the longest string, 1.0 for identical strings.
+fn ratio(a: &str, b: &str) -> f64 {
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(a, b) as f64 / longest as f64
+}
+
+fn join(tokens: impl IntoIterator<Item = impl AsRef<str>>) -> String {
+    tokens
+        .into_iter()
+        .map(|t| t.as_ref().to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Ratio of the strings with their words sorted, so word order doesn't matter.
+pub fn token_sort_ratio(a: &str, b: &str) -> f64 {
+    let a: BTreeSet<&str> = a.split_whitespace().collect();
+    let b: BTreeSet<&str> = b.split_whitespace().collect();
+    ratio(&join(a), &join(b))
+}
+
+/// Ratio of the words both strings have against each string's words, so extra words
+/// on one side don't count against it.
+pub fn token_set_ratio(a: &str, b: &str) -> f64 {
+    let a: BTreeSet<&str> = a.split_whitespace().collect();
+    let b: BTreeSet<&str> = b.split_whitespace().collect();
+    let common = join(a.intersection(&b));
+    let with_a = join(a.intersection(&b).chain(a.difference(&b)));
+    let with_b = join(a.intersection(&b).chain(b.difference(&a)));
+    if common.is_empty() {
+        return ratio(&with_a, &with_b);
+    }
+    ratio(&common, &with_a)
+        .max(ratio(&common, &with_b))
+        .max(ratio(&with_a, &with_b))
+}
+
+/// Both ratios count equally, so a title that is only part of the other ("love" and
+/// "love me do") doesn't pass for the same title.
+pub fn token_similarity(a: &str, b: &str) -> f64 {
+    (token_sort_ratio(a, b) + token_set_ratio(a, b)) / 2.0
+}
+
+/// Compares words rather than characters, relative to the length of the strings, so
+/// that a reordered or slightly longer title still matches while two different short
+/// titles don't.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchTokenSimilarity {
+    /// Minimum similarity, between 0.0 and 1.0, of both the artist and the title.
+    pub threshold: f64,
+}
+
+impl Default for MatchTokenSimilarity {
+    fn default() -> Self {
+        Self { threshold: 0.85 }
+    }
+}
+
+#[async_trait::async_trait]
+impl Matcher for MatchTokenSimilarity {
+    async fn match_fn(
+        &self,
+        plex_tracks: &PlexIndex,
+        spotify_track: &TrackAlbumArtist,
+        config: &MatchConfig,
+    ) -> Result<MatchResult, anyhow::Error> {
+        let spotify_artists: Vec<String> = spotify_track
+            .credited_artists()
+            .map(|a| config.artist_aliases.canonical(a))
+            .collect();
+        let spotify_title = comparison_key(&spotify_track.track);
+
+        let scored: Vec<(f64, &TrackAlbumArtist)> = plex_tracks
+            .candidates(spotify_track, config)
+            .into_iter()
+            .filter(|p| within_tolerance(spotify_track, p, config) && compatible(spotify_track, p))
+            .filter_map(|p| {
+                let title = token_similarity(&spotify_title, &comparison_key(&p.track));
+                if title < self.threshold {
+                    return None;
+                }
+                let artist = p
+                    .credited_artists()
+                    .map(|a| config.artist_aliases.canonical(a))
+                    .flat_map(|a| spotify_artists.iter().map(move |s| token_similarity(s, &a)))
+                    .fold(0.0, f64::max);
+                (artist >= self.threshold).then_some((artist + title, p))
+            })
+            .collect();
+
+        let best = scored.iter().map(|(s, _)| *s).fold(f64::MIN, f64::max);
+        let closest: Vec<&TrackAlbumArtist> = scored
+            .iter()
+            .filter(|(s, _)| best - s <= SCORE_EPSILON)
+            .map(|(_, p)| *p)
+            .collect();
+
+        if let Some(plex_track) = best_candidate(&closest, spotify_track, config) {
+            let explanation = MatchExplanation::new(
+                "token similarity",
+                format!("{} - {}", spotify_artists.join(", "), spotify_title),
+                format!(
+                    "{} - {}",
+                    plex_track
+                        .artist
+                        .iter()
+                        .map(|a| config.artist_aliases.canonical(a))
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                    comparison_key(&plex_track.track)
+                ),
+                best / 2.0,
+            );
+            return Ok(MatchResult::new(plex_track, explanation));
+        }
+        Err(anyhow::anyhow!("No match found"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_similarity() {
+        let similar = [
+            ("sound of silence", "the sound of silence"),
+            ("ryuichi sakamoto", "sakamoto ryuichi"),
+            ("dont stop me now", "dont stop me now"),
+            ("i will always love you", "always love you i will"),
+        ];
+        for (a, b) in similar {
+            assert!(token_similarity(a, b) >= 0.85, "{} ~ {}", a, b);
+        }
+        let different = [
+            ("love", "love me do"),
+            ("cat", "dog"),
+            ("yes", "yea"),
+            ("hello", "hello goodbye"),
+        ];
+        for (a, b) in different {
+            assert!(token_similarity(a, b) < 0.85, "{} !~ {}", a, b);
+        }
+    }
+}
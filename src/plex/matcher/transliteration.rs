@@ -0,0 +1,87 @@
+use crate::{
+    normalize::comparison_key,
+    track_album_artist::TrackAlbumArtist,
+    transliterate::{has_non_latin, romanize},
+};
+
+use super::{
+    forward_backward::MatchForwardBack, index::PlexIndex, levenshtein::LevenshteinDistance,
+    MatchConfig, MatchResult, Matcher,
+};
+
+pub(super) fn compact(s: &str) -> String {
+    s.split_whitespace().collect()
+}
+
+/// Romanized forms of an artist, native names have no spaces between the family and
+/// given name, so the romanized name is also tried in reversed order, e.g. both
+/// "ryuichisakamoto" and "sakamotoryuichi".
+fn artist_variants(artist: &str, config: &MatchConfig) -> Vec<String> {
+    let romanized = comparison_key(&romanize(artist, &config.transliterations));
+    let mut variants = vec![compact(&romanized)];
+    let tokens: Vec<&str> = romanized.split_whitespace().collect();
+    if tokens.len() > 1 {
+        variants.push(tokens.iter().rev().copied().collect());
+    }
+    variants
+}
+
+pub(super) fn romanize_track(track: &TrackAlbumArtist, config: &MatchConfig) -> TrackAlbumArtist {
+    let mut track = track.clone();
+    let variants = |artists: &[String]| {
+        artists
+            .iter()
+            .flat_map(|a| artist_variants(a, config))
+            .collect()
+    };
+    track.artist = variants(&track.artist);
+    track.featured_artist = variants(&track.featured_artist);
+    track.track = compact(&comparison_key(&romanize(
+        &track.track,
+        &config.transliterations,
+    )));
+    track
+}
+
+pub(super) fn is_non_latin(track: &TrackAlbumArtist) -> bool {
+    has_non_latin(&track.track) || track.credited_artists().any(|a| has_non_latin(a))
+}
+
+/// Compares romanized forms of both sides, for artists that are in native script on
+/// one side and romanized on the other.
+#[derive(Default)]
+pub struct MatchTransliterated {
+    /// The configured matchers compared on the romanized forms.
+    pub forward_backward: MatchForwardBack,
+    pub levenshtein: LevenshteinDistance,
+}
+#[async_trait::async_trait]
+impl Matcher for MatchTransliterated {
+    async fn match_fn(
+        &self,
+        plex_tracks: &PlexIndex,
+        spotify_track: &TrackAlbumArtist,
+        config: &MatchConfig,
+    ) -> Result<MatchResult, anyhow::Error> {
+        // a romanized spotify track can only match a plex track in another script.
+        let spotify_non_latin = is_non_latin(spotify_track);
+        let candidates = plex_tracks.candidates(spotify_track, config);
+        let new_plex_tracks: Vec<TrackAlbumArtist> = candidates
+            .iter()
+            .filter(|p| spotify_non_latin || is_non_latin(p))
+            .map(|p| romanize_track(p, config))
+            .collect();
+        let new_plex_tracks: Vec<&TrackAlbumArtist> = new_plex_tracks.iter().collect();
+        let spotify_track = romanize_track(spotify_track, config);
+
+        let result = self
+            .forward_backward
+            .find(&new_plex_tracks, &spotify_track, config)
+            .or_else(|| {
+                self.levenshtein
+                    .find(&new_plex_tracks, &spotify_track, config)
+            })
+            .ok_or_else(|| anyhow::anyhow!("No match found"))?;
+        Ok(result.original(&candidates).after("romanized"))
+    }
+}
@@ -0,0 +1,30 @@
+use crate::{
+    track_album_artist::TrackAlbumArtist,
+    track_version::{TrackVersion, VersionKind},
+};
+
+fn kind(version: &Option<TrackVersion>) -> Option<VersionKind> {
+    version.as_ref().map(|v| v.kind)
+}
+
+/// Whether two tracks are the same version of a song. A remaster is the same recording
+/// as the original, anything else (live, acoustic, remix...) has to agree.
+pub fn compatible(spotify_track: &TrackAlbumArtist, plex_track: &TrackAlbumArtist) -> bool {
+    match (kind(&spotify_track.version), kind(&plex_track.version)) {
+        (None, Some(VersionKind::Remaster)) | (Some(VersionKind::Remaster), None) => true,
+        (a, b) => a == b,
+    }
+}
+
+/// From 1.0 for the exact same version down to 0.0 for a different one.
+pub fn version_similarity(spotify_track: &TrackAlbumArtist, plex_track: &TrackAlbumArtist) -> f64 {
+    if spotify_track.version == plex_track.version {
+        1.0
+    } else if kind(&spotify_track.version) == kind(&plex_track.version) {
+        0.75
+    } else if compatible(spotify_track, plex_track) {
+        0.5
+    } else {
+        0.0
+    }
+}
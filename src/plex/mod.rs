@@ -1,11 +1,29 @@
+use std::{collections::HashSet, time::Duration};
+
 use client::Plex;
+use log::{debug, info, warn};
 use models::{metadata::Metadata, providers::ProviderMediaContainer, MediaContainerWrapper};
+use tokio::time::Instant;
 
-use crate::track_album_artist::{MetadataType, PlexMetadata, TrackAlbumArtist};
+use crate::{
+    artist_credit::{ArtistCredit, ArtistCreditParser},
+    logging::Progress,
+    normalize::normalize,
+    track_album_artist::{MediaInfo, MetadataType, PlexMetadata, TrackAlbumArtist},
+    track_version::{parse_version, TrackVersion},
+};
 
 pub mod client;
 pub mod matcher;
 pub mod models;
+pub mod webhook;
+
+/// Activities of a library section being scanned.
+const SCAN_ACTIVITIES: [&str; 2] = ["library.update.section", "library.refresh.items"];
+const REFRESH_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How long plex may take to start scanning after the refresh was asked for.
+const REFRESH_START_TIMEOUT: Duration = Duration::from_secs(60);
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(30 * 60);
 
 fn get_music_provider(providers: &MediaContainerWrapper<ProviderMediaContainer>) -> Option<String> {
     for provider in providers.media_container.media_provider.iter() {
@@ -25,122 +43,327 @@ fn get_music_provider(providers: &MediaContainerWrapper<ProviderMediaContainer>)
     None
 }
 
-pub async fn get_plex_tracks(plex: &Plex) -> Result<Vec<TrackAlbumArtist>, anyhow::Error> {
-    let mut tracks = Vec::new();
-    // access to plex.
-
+/// Changes whenever tracks are added to, changed in or removed from the music library,
+/// so a scan can be reused as long as it stays the same.
+pub async fn get_library_version(plex: &Plex) -> Result<Option<i64>, anyhow::Error> {
     let providers = plex.get_providers().await?;
-
-    // find provider with name "Music"
     let provider = get_music_provider(&providers).expect("no music provider found");
+    let sections = plex.get_sections().await?;
+    let version = sections
+        .media_container
+        .directories
+        .iter()
+        .flatten()
+        .find(|section| section.key == provider)
+        .and_then(|section| section.content_changed_at.or(section.updated_at));
+    Ok(version)
+}
+
+/// The music library's tracks by artist, so that single artists can be scanned again
+/// when tracks are added to them.
+pub struct PlexLibrary {
+    providers: MediaContainerWrapper<ProviderMediaContainer>,
+    provider: String,
+    /// Artist names, band names from the library mustn't be split into several artists.
+    artist_names: Vec<String>,
+    /// By artist rating key, in the order of the library.
+    artists: Vec<(String, Vec<TrackAlbumArtist>)>,
+}
+
+impl PlexLibrary {
+    pub async fn scan(plex: &Plex) -> Result<Self, anyhow::Error> {
+        // access to plex.
+
+        let providers = plex.get_providers().await?;
+
+        // find provider with name "Music"
+        let provider = get_music_provider(&providers).expect("no music provider found");
+
+        let artists_final = get_artists(plex, &provider).await?;
+
+        let parser = ArtistCreditParser::new(artists_final.iter().map(|a| &a.title));
+
+        // get total.
+        //println!("Artists Total: {:?}", artists_final.len());
+        let mut artists = vec![];
+        let mut found = 0;
+        let mut progress = Progress::new("Processing Plex artists", artists_final.len() as u64);
+        for artist in artists_final.iter() {
+            let tracks = get_artist_tracks(plex, &provider, &providers, &parser, artist).await?;
+            found += tracks.len();
+            artists.push((artist.rating_key.clone(), tracks));
+            progress.inc(format!("tracks found: {}", found));
+        }
+        progress.finish();
+
+        Ok(Self {
+            providers,
+            provider,
+            artist_names: artists_final.into_iter().map(|a| a.title).collect(),
+            artists,
+        })
+    }
 
+    /// Scans the artist again, or adds it when it is new to the library.
+    pub async fn rescan_artist(
+        &mut self,
+        plex: &Plex,
+        rating_key: &str,
+    ) -> Result<(), anyhow::Error> {
+        let artist = plex
+            .get_metadata(rating_key)
+            .await?
+            .media_container
+            .metadata;
+        let Some(artist) = artist.and_then(|a| a.into_iter().next()) else {
+            // removed from the library.
+            self.artists.retain(|(key, _)| key != rating_key);
+            return Ok(());
+        };
+        if !self.artist_names.contains(&artist.title) {
+            self.artist_names.push(artist.title.clone());
+        }
+        let parser = ArtistCreditParser::new(self.artist_names.iter());
+        let tracks =
+            get_artist_tracks(plex, &self.provider, &self.providers, &parser, &artist).await?;
+        match self.artists.iter_mut().find(|(key, _)| key == rating_key) {
+            Some((_, artist_tracks)) => *artist_tracks = tracks,
+            None => self.artists.push((rating_key.to_string(), tracks)),
+        }
+        Ok(())
+    }
+
+    /// Scans the artists with these normalized names again, e.g. the ones of downloaded
+    /// tracks, including the ones new to the library.
+    pub async fn rescan_artists_named(
+        &mut self,
+        plex: &Plex,
+        names: &HashSet<String>,
+    ) -> Result<(), anyhow::Error> {
+        for artist in get_artists(plex, &self.provider).await? {
+            if names.contains(&normalize(&artist.title)) {
+                self.rescan_artist(plex, &artist.rating_key).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn tracks(&self) -> Vec<TrackAlbumArtist> {
+        self.artists
+            .iter()
+            .flat_map(|(_, tracks)| tracks.iter().cloned())
+            .collect()
+    }
+}
+
+async fn get_artists(plex: &Plex, provider: &str) -> Result<Vec<Metadata>, anyhow::Error> {
     let mut artists_final: Vec<Metadata> = vec![];
     let mut offset = 0;
     let mut total = 1;
     while offset < total {
-        let artists = plex.get_artists(provider.clone(), offset).await?;
+        let artists = plex.get_artists(provider.to_string(), offset).await?;
         total = artists.media_container.total_size.unwrap();
         offset += artists.media_container.size;
         artists_final.extend(artists.media_container.metadata.unwrap());
     }
+    Ok(artists_final)
+}
 
-    // get total.
-    //println!("Artists Total: {:?}", artists_final.len());
-    let mut i = 1;
-    for artist in artists_final.iter() {
-        //println!("artist: {:?}", artist.title);
-        // get any additional metadata from artist.
-        let mut metadata: Vec<Metadata> = Vec::new();
-        let extras = plex
-            .get_extra_items(&provider, &artist.rating_key, 9) // 9 is albums
-            .await?;
-        if extras.media_container.metadata.is_some() {
-            for album in extras.media_container.metadata.as_ref().unwrap().iter() {
-                metadata.push(album.clone());
-            }
+/// The scan activity of the library section, if plex is scanning it.
+async fn scan_activity(
+    plex: &Plex,
+    section: &str,
+) -> Result<Option<models::activities::Activity>, anyhow::Error> {
+    let activities = plex.get_activities().await?.media_container.activities;
+    Ok(activities.into_iter().flatten().find(|a| {
+        SCAN_ACTIVITIES.contains(&a.rtype.as_str()) && a.section().as_deref() == Some(section)
+    }))
+}
+
+/// Asks plex to scan the music library for new files, only the folder `path` when given,
+/// and waits until it is done. The scan only shows up in the activities a moment later,
+/// so it first waits for the scan to start, or for the library to have changed already
+/// when the scan was over before it could be seen.
+pub async fn refresh_library(plex: &Plex, path: Option<&str>) -> Result<(), anyhow::Error> {
+    let providers = plex.get_providers().await?;
+    let section = get_music_provider(&providers).expect("no music provider found");
+    let version = get_library_version(plex).await?;
+    plex.refresh_section(&section, path).await?;
+    info!("Waiting for plex to scan the library");
+
+    let deadline = Instant::now() + REFRESH_START_TIMEOUT;
+    loop {
+        if scan_activity(plex, &section).await?.is_some() {
+            break;
         }
-        // let albums = plex.get_metadata_children(&artist.rating_key).await?;
-        // if albums.media_container.metadata.is_some() {
-        //     for album in albums.media_container.metadata.as_ref().unwrap().iter() {
-        //         metadata.push(album.clone());
-        //     }
-        // }
-
-        let mut tracks_metadata: Vec<Metadata> = Vec::new();
-        for meta in metadata {
-            //println!("\talbum: {:?} ({})", meta.title, meta.rating_key);
-            let tracks_meta = plex.get_metadata_children(&meta.rating_key).await?;
-            if tracks_meta.media_container.metadata.is_none() {
-                continue;
-            }
-            for item in tracks_meta.media_container.metadata.unwrap().iter() {
-                tracks_metadata.push(item.clone());
+        if get_library_version(plex).await? != version {
+            return Ok(());
+        }
+        if Instant::now() > deadline {
+            warn!(
+                "plex didn't start scanning within {:?}, going on without it",
+                REFRESH_START_TIMEOUT
+            );
+            return Ok(());
+        }
+        tokio::time::sleep(REFRESH_POLL_INTERVAL).await;
+    }
+
+    let deadline = Instant::now() + REFRESH_TIMEOUT;
+    loop {
+        match scan_activity(plex, &section).await? {
+            None => return Ok(()),
+            Some(_) if Instant::now() > deadline => {
+                anyhow::bail!("plex didn't finish scanning within {:?}", REFRESH_TIMEOUT)
             }
+            Some(activity) => debug!(
+                "{} {}%",
+                activity.title.as_deref().unwrap_or(&activity.rtype),
+                activity.progress.unwrap_or(0)
+            ),
         }
+        tokio::time::sleep(REFRESH_POLL_INTERVAL).await;
+    }
+}
 
-        let singles_eps = plex
-            .get_extra_items(&provider, &artist.rating_key, 10) // 10 is tracks
-            .await?;
+async fn get_artist_tracks(
+    plex: &Plex,
+    provider: &String,
+    providers: &MediaContainerWrapper<ProviderMediaContainer>,
+    parser: &ArtistCreditParser,
+    artist: &Metadata,
+) -> Result<Vec<TrackAlbumArtist>, anyhow::Error> {
+    let mut tracks = Vec::new();
+    //println!("artist: {:?}", artist.title);
+    // get any additional metadata from artist.
+    let mut metadata: Vec<Metadata> = Vec::new();
+    let extras = plex
+        .get_extra_items(provider, &artist.rating_key, 9) // 9 is albums
+        .await?;
+    if extras.media_container.metadata.is_some() {
+        for album in extras.media_container.metadata.as_ref().unwrap().iter() {
+            metadata.push(album.clone());
+        }
+    }
+    // let albums = plex.get_metadata_children(&artist.rating_key).await?;
+    // if albums.media_container.metadata.is_some() {
+    //     for album in albums.media_container.metadata.as_ref().unwrap().iter() {
+    //         metadata.push(album.clone());
+    //     }
+    // }
 
-        if singles_eps.media_container.metadata.is_some() {
-            for track in singles_eps
-                .media_container
-                .metadata
-                .as_ref()
-                .unwrap()
-                .iter()
-            {
-                tracks_metadata.push(track.clone());
-            }
+    let mut tracks_metadata: Vec<Metadata> = Vec::new();
+    for meta in metadata {
+        //println!("\talbum: {:?} ({})", meta.title, meta.rating_key);
+        let tracks_meta = plex.get_metadata_children(&meta.rating_key).await?;
+        if tracks_meta.media_container.metadata.is_none() {
+            continue;
+        }
+        for item in tracks_meta.media_container.metadata.unwrap().iter() {
+            tracks_metadata.push(item.clone());
         }
+    }
 
-        // artist and split that original title.
+    let singles_eps = plex
+        .get_extra_items(provider, &artist.rating_key, 10) // 10 is tracks
+        .await?;
 
-        for track in tracks_metadata.iter() {
-            let mut artists: Vec<String> = vec![artist.title.trim().to_lowercase()];
-            if track.original_title.is_some() {
-                let extra_artists = track
-                    .original_title
-                    .as_ref()
-                    .unwrap()
-                    .split(&[',', '&', '/'][..])
-                    .map(|s| s.trim().to_lowercase())
-                    .collect::<Vec<String>>();
-                artists.extend(extra_artists);
-            }
+    if singles_eps.media_container.metadata.is_some() {
+        for track in singles_eps
+            .media_container
+            .metadata
+            .as_ref()
+            .unwrap()
+            .iter()
+        {
+            tracks_metadata.push(track.clone());
+        }
+    }
+
+    // artist and split that original title.
 
-            let track_album_artist = get_track_album_artist(track, artists, providers.clone());
-            tracks.push(track_album_artist);
+    for track in tracks_metadata.iter() {
+        let mut credit = ArtistCredit {
+            primary: vec![normalize(&artist.title)],
+            featured: vec![],
+        };
+        if let Some(original_title) = &track.original_title {
+            credit.merge(parser.parse_artists(original_title));
         }
+        let (title, featured) = parser.parse_title(&track.title);
+        let (title, version) = parse_version(&title);
+        credit.merge(ArtistCredit {
+            primary: vec![],
+            featured,
+        });
 
-        print!(
-            "\rProcessing Plex Artist {} of {}, tracks found: {}               ",
-            i,
-            artists_final.len(),
-            tracks.len()
-        );
-        i += 1;
+        let track_album_artist =
+            get_track_album_artist(track, title, version, credit, providers.clone());
+        tracks.push(track_album_artist);
     }
-    println!("");
 
     Ok(tracks)
 }
 
+/// Agents that know the ISRC of a track expose it as an `isrc://` guid, either as the
+/// track guid or in the list of external guids.
+fn get_isrc(track: &Metadata) -> Option<String> {
+    get_guids(track)
+        .find_map(|guid| guid.strip_prefix("isrc://"))
+        .map(|isrc| isrc.trim().to_uppercase())
+}
+
+/// Picard tagged files get `mbid://` guids with their MusicBrainz recording ids.
+fn get_musicbrainz_ids(track: &Metadata) -> Vec<String> {
+    get_guids(track)
+        .filter_map(|guid| guid.strip_prefix("mbid://"))
+        .map(|mbid| mbid.trim().to_lowercase())
+        .collect()
+}
+
+fn get_guids(track: &Metadata) -> impl Iterator<Item = &String> {
+    track
+        .guid
+        .iter()
+        .chain(track.guids.iter().flatten().map(|g| &g.id))
+}
+
+fn get_media(track: &Metadata) -> Vec<MediaInfo> {
+    track
+        .media
+        .iter()
+        .flatten()
+        .map(|media| MediaInfo {
+            codec: media.audio_codec.as_ref().map(|c| c.to_lowercase()),
+            bitrate: media.bitrate,
+            channels: media.audio_channels,
+            file: media
+                .parts
+                .iter()
+                .flatten()
+                .find_map(|part| part.file.clone()),
+        })
+        .collect()
+}
+
 fn get_track_album_artist(
     track: &Metadata,
-    artist: Vec<String>,
+    title: String,
+    version: Option<TrackVersion>,
+    credit: ArtistCredit,
     providers: MediaContainerWrapper<ProviderMediaContainer>,
 ) -> TrackAlbumArtist {
     let track_album_artist = TrackAlbumArtist {
-        track: track.title.trim().to_lowercase(),
-        album: track
-            .parent_title
-            .as_ref()
-            .unwrap()
-            .trim()
-            .to_lowercase()
-            .to_string(),
-        artist,
+        track: title,
+        version,
+        album: normalize(track.parent_title.as_ref().unwrap()),
+        artist: credit.primary,
+        featured_artist: credit.featured,
+        duration_ms: track.duration,
+        track_number: track.index,
+        disc_number: track.parent_index,
+        isrc: get_isrc(track),
+        musicbrainz_ids: get_musicbrainz_ids(track),
         metadata: MetadataType::Plex(PlexMetadata {
             machine_identifier: providers.media_container.machine_identifier.clone(),
             provider_identifier: providers
@@ -152,6 +375,7 @@ fn get_track_album_artist(
                 .clone(),
             rating_key: track.rating_key.clone(),
             key: track.key.clone(),
+            media: get_media(track),
         }),
     };
 
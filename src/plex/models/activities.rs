@@ -0,0 +1,36 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ActivityMediaContainer {
+    #[serde(rename = "Activity")]
+    pub activities: Option<Vec<Activity>>,
+}
+
+/// Something the server is busy with, like scanning a library section.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Activity {
+    #[serde(rename = "type")]
+    pub rtype: String,
+    pub title: Option<String>,
+    pub progress: Option<u32>,
+    #[serde(rename = "Context")]
+    pub context: Option<ActivityContext>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ActivityContext {
+    /// A string or a number depending on the server version.
+    #[serde(rename = "librarySectionID")]
+    pub library_section_id: Option<Value>,
+}
+
+impl Activity {
+    pub fn section(&self) -> Option<String> {
+        match self.context.as_ref()?.library_section_id.as_ref()? {
+            Value::String(id) => Some(id.clone()),
+            Value::Number(id) => Some(id.to_string()),
+            _ => None,
+        }
+    }
+}
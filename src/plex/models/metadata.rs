@@ -14,9 +14,37 @@ pub struct MetadataMediaContainer {
 pub struct Metadata {
     pub key: String,
     pub rating_key: String,
+    pub guid: Option<String>,
     pub title: String,
     pub parent_title: Option<String>,
     #[serde(rename = "type")]
     pub rtype: String,
     pub original_title: Option<String>,
+    pub duration: Option<u64>,
+    pub index: Option<u32>,
+    pub parent_index: Option<u32>,
+    #[serde(rename = "Guid")]
+    pub guids: Option<Vec<Guid>>,
+    #[serde(rename = "Media")]
+    pub media: Option<Vec<Media>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Guid {
+    pub id: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Media {
+    pub bitrate: Option<u32>,
+    pub audio_channels: Option<u32>,
+    pub audio_codec: Option<String>,
+    #[serde(rename = "Part")]
+    pub parts: Option<Vec<Part>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Part {
+    pub file: Option<String>,
 }
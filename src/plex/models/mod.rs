@@ -1,8 +1,10 @@
 use serde::Deserialize;
 
+pub mod activities;
 pub mod extras;
 pub mod metadata;
 pub mod providers;
+pub mod sections;
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct MediaContainerWrapper<T> {
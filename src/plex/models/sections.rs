@@ -0,0 +1,17 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SectionMediaContainer {
+    #[serde(rename = "Directory")]
+    pub directories: Option<Vec<Section>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Section {
+    pub key: String,
+    pub updated_at: Option<i64>,
+    /// Bumped by the scanner whenever tracks are added, changed or removed.
+    pub content_changed_at: Option<i64>,
+}
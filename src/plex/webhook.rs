@@ -0,0 +1,200 @@
+use axum::{
+    extract::{Multipart, Query, State},
+    http::StatusCode,
+    routing::post,
+    Router,
+};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use tokio::{net::TcpListener, sync::mpsc::UnboundedSender};
+
+/// The json plex posts in the `payload` field of its multipart webhook requests.
+#[derive(Deserialize, Debug)]
+pub struct Payload {
+    pub event: String,
+    #[serde(rename = "Metadata")]
+    pub metadata: Option<PayloadMetadata>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PayloadMetadata {
+    /// `artist` for music libraries.
+    pub library_section_type: Option<String>,
+    #[serde(rename = "type")]
+    pub rtype: String,
+    pub rating_key: String,
+    pub parent_rating_key: Option<String>,
+    pub grandparent_rating_key: Option<String>,
+}
+
+impl Payload {
+    /// The rating key of the artist new tracks were added to, when it is a
+    /// `library.new` event of the music library.
+    pub fn new_tracks_artist(&self) -> Option<&String> {
+        let metadata = self.metadata.as_ref()?;
+        if self.event != "library.new" || metadata.library_section_type.as_deref() != Some("artist")
+        {
+            return None;
+        }
+        match metadata.rtype.as_str() {
+            "track" => metadata.grandparent_rating_key.as_ref(),
+            "album" => metadata.parent_rating_key.as_ref(),
+            "artist" => Some(&metadata.rating_key),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Listener {
+    artists: UnboundedSender<String>,
+    token: String,
+}
+
+/// The secret the webhook url carries, e.g. `http://host:8585/?token=...`.
+#[derive(Deserialize)]
+struct Auth {
+    token: Option<String>,
+}
+
+/// Accepts the webhook posts of plex that carry the token and sends the artists new
+/// tracks were added to.
+pub fn router(artists: UnboundedSender<String>, token: &str) -> Router {
+    Router::new()
+        .route("/", post(webhook))
+        .with_state(Listener {
+            artists,
+            token: token.to_string(),
+        })
+}
+
+async fn webhook(
+    State(listener): State<Listener>,
+    Query(auth): Query<Auth>,
+    mut multipart: Multipart,
+) -> StatusCode {
+    if listener.token.is_empty() || auth.token.as_deref() != Some(listener.token.as_str()) {
+        warn!("Plex webhook request without the token");
+        return StatusCode::UNAUTHORIZED;
+    }
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.name() != Some("payload") {
+            continue;
+        }
+        let payload: Payload = match field.text().await.map(|text| serde_json::from_str(&text)) {
+            Ok(Ok(payload)) => payload,
+            _ => {
+                warn!("Invalid plex webhook payload");
+                return StatusCode::BAD_REQUEST;
+            }
+        };
+        debug!("Plex webhook: {}", payload.event);
+        if let Some(artist) = payload.new_tracks_artist() {
+            info!("New tracks in the plex library for artist {}", artist);
+            let _ = listener.artists.send(artist.clone());
+        }
+        return StatusCode::OK;
+    }
+    StatusCode::BAD_REQUEST
+}
+
+/// Serves the webhook until the runtime shuts down.
+pub async fn serve(
+    listener: TcpListener,
+    artists: UnboundedSender<String>,
+    token: &str,
+) -> Result<(), anyhow::Error> {
+    info!("Listening for plex webhooks on {}", listener.local_addr()?);
+    axum::serve(listener, router(artists, token)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    fn fixture(name: &str) -> String {
+        std::fs::read_to_string(format!(
+            "{}/tests/fixtures/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_new_tracks_artist() {
+        let cases = [
+            ("plex_webhook_library_new.json", Some("48211")),
+            ("plex_webhook_library_new_album.json", Some("51019")),
+            ("plex_webhook_library_new_movie.json", None),
+            ("plex_webhook_media_play.json", None),
+        ];
+        for (name, expected) in cases {
+            let payload: Payload = serde_json::from_str(&fixture(name)).unwrap();
+            assert_eq!(
+                payload.new_tracks_artist().map(|a| a.as_str()),
+                expected,
+                "{}",
+                name
+            );
+        }
+    }
+
+    /// Posts the payload the way plex does, as a multipart form along with a thumbnail.
+    async fn post(uri: &str, payload: &str) -> (StatusCode, Option<String>) {
+        let boundary = "plexwebhookboundary";
+        let body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"payload\"\r\nContent-Type: application/json\r\n\r\n{payload}\r\n--{b}\r\nContent-Disposition: form-data; name=\"thumb\"; filename=\"image.jpg\"\r\nContent-Type: image/jpeg\r\n\r\nJFIF\r\n--{b}--\r\n",
+            b = boundary,
+            payload = payload
+        );
+        let request = Request::post(uri)
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .body(Body::from(body))
+            .unwrap();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let response = router(sender, "secret").oneshot(request).await.unwrap();
+        (response.status(), receiver.try_recv().ok())
+    }
+
+    #[tokio::test]
+    async fn test_webhook() {
+        let library_new = fixture("plex_webhook_library_new.json");
+        let media_play = fixture("plex_webhook_media_play.json");
+        let cases = [
+            (
+                "/?token=secret",
+                library_new.as_str(),
+                StatusCode::OK,
+                Some("48211"),
+            ),
+            ("/?token=secret", media_play.as_str(), StatusCode::OK, None),
+            ("/?token=secret", "not json", StatusCode::BAD_REQUEST, None),
+            ("/", library_new.as_str(), StatusCode::UNAUTHORIZED, None),
+            (
+                "/?token=",
+                library_new.as_str(),
+                StatusCode::UNAUTHORIZED,
+                None,
+            ),
+            (
+                "/?token=guess",
+                library_new.as_str(),
+                StatusCode::UNAUTHORIZED,
+                None,
+            ),
+        ];
+        for (uri, payload, expected_status, expected_artist) in cases {
+            let (status, artist) = post(uri, payload).await;
+            assert_eq!(status, expected_status, "{}", uri);
+            assert_eq!(artist.as_deref(), expected_artist, "{}", uri);
+        }
+    }
+}
@@ -0,0 +1,436 @@
+use std::{collections::HashMap, fmt::Write as _, path::Path};
+
+use serde::Serialize;
+
+use crate::{
+    plex::matcher::{
+        duplicates::Duplicate, explain::MatchExplanation, index::PlexIndex, MatchResult,
+    },
+    track_album_artist::{MetadataType, TrackAlbumArtist},
+    unmatched,
+};
+
+/// Levenshtein matches further away than this are worth checking.
+const LOW_CONFIDENCE_DISTANCE: f64 = 2.0;
+/// Token similarity matches below this are worth checking.
+const LOW_CONFIDENCE_SIMILARITY: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    Matched,
+    /// Matched, but by a fuzzy matcher and with names far enough apart to be checked.
+    LowConfidence,
+    Unmatched,
+    /// Matched to a plex track that another spotify track was matched to.
+    Duplicate,
+    /// The match of the pipeline was changed to another one by the one-to-one assignment,
+    /// tracks it left without a match are unmatched.
+    Overridden,
+}
+
+impl Status {
+    fn name(&self) -> &'static str {
+        match self {
+            Status::Matched => "matched",
+            Status::LowConfidence => "low-confidence",
+            Status::Unmatched => "unmatched",
+            Status::Duplicate => "duplicate",
+            Status::Overridden => "overridden",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ReportEntry {
+    /// Position in the spotify playlist, from 1.
+    pub position: usize,
+    pub status: Status,
+    /// Whether the plex track was added to the playlist.
+    pub added: bool,
+    pub spotify: TrackAlbumArtist,
+    pub plex: Option<TrackAlbumArtist>,
+    pub explanation: Option<MatchExplanation>,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    pub playlist: String,
+    pub entries: Vec<ReportEntry>,
+}
+
+/// A report entry on one line, for the csv.
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    position: usize,
+    status: &'static str,
+    added: bool,
+    spotify_artist: String,
+    spotify_title: &'a str,
+    spotify_album: &'a str,
+    spotify_uri: &'a str,
+    spotify_url: &'a str,
+    plex_artist: String,
+    plex_title: &'a str,
+    plex_album: &'a str,
+    plex_rating_key: &'a str,
+    plex_file: &'a str,
+    matcher: &'a str,
+    method: &'a str,
+    score: Option<f64>,
+    transformations: String,
+}
+
+fn is_low_confidence(explanation: &MatchExplanation) -> bool {
+    if explanation.other_version {
+        return true;
+    }
+    match explanation.method.as_str() {
+        "levenshtein distance" => explanation.score > LOW_CONFIDENCE_DISTANCE,
+        "token similarity" => explanation.score < LOW_CONFIDENCE_SIMILARITY,
+        _ => false,
+    }
+}
+
+fn file(track: &TrackAlbumArtist) -> Option<&String> {
+    match &track.metadata {
+        MetadataType::Plex(meta) => meta.media.iter().find_map(|m| m.file.as_ref()),
+        MetadataType::Spotify(_) => None,
+    }
+}
+
+/// The playlist name with anything that can't be in a file name replaced.
+pub fn file_name(playlist: &str) -> String {
+    playlist
+        .chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() || c == '-' || c == '_' || c == ' ' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Report {
+    /// `picked` are the matches of the pipeline, `results` the ones left after the
+    /// one-to-one assignment and `added` whether the duplicate policy kept them.
+    pub fn new(
+        playlist: &str,
+        spotify_tracks: &[TrackAlbumArtist],
+        picked: &[Option<MatchResult>],
+        results: &[Option<MatchResult>],
+        duplicates: &[Duplicate],
+        added: &[bool],
+        plex_tracks: &PlexIndex,
+    ) -> Self {
+        // matchers may return the plex track with transformed names, report the original.
+        let originals: HashMap<&String, &TrackAlbumArtist> =
+            plex_tracks.tracks().iter().map(|p| (p.id(), p)).collect();
+        let entries = spotify_tracks
+            .iter()
+            .enumerate()
+            .map(|(i, spotify_track)| {
+                let id = |r: &Option<MatchResult>| r.as_ref().map(|r| r.track.id().clone());
+                let status = match &results[i] {
+                    None => Status::Unmatched,
+                    _ if id(&picked[i]) != id(&results[i]) => Status::Overridden,
+                    Some(_) if !added[i] => Status::Duplicate,
+                    Some(_) if matches!(duplicates[i], Duplicate::Collision { .. }) => {
+                        Status::Duplicate
+                    }
+                    Some(result) if is_low_confidence(&result.explanation) => Status::LowConfidence,
+                    Some(_) => Status::Matched,
+                };
+                ReportEntry {
+                    position: i + 1,
+                    status,
+                    added: results[i].is_some() && added[i],
+                    spotify: spotify_track.clone(),
+                    plex: results[i].as_ref().map(|r| {
+                        originals
+                            .get(r.track.id())
+                            .map(|t| (*t).clone())
+                            .unwrap_or_else(|| r.track.clone())
+                    }),
+                    explanation: results[i].as_ref().map(|r| r.explanation.clone()),
+                }
+            })
+            .collect();
+        Self {
+            playlist: playlist.to_string(),
+            entries,
+        }
+    }
+
+    pub fn count(&self, status: Status) -> usize {
+        self.entries.iter().filter(|e| e.status == status).count()
+    }
+
+    /// Writes `<playlist>.json`, `<playlist>.csv` and `<playlist>.html` to the directory.
+    pub fn write(&self, dir: &str) -> Result<(), anyhow::Error> {
+        std::fs::create_dir_all(dir)?;
+        let path = Path::new(dir).join(file_name(&self.playlist));
+        self.write_json(&path.with_extension("json"))?;
+        self.write_csv(&path.with_extension("csv"))?;
+        self.write_html(&path.with_extension("html"))?;
+        Ok(())
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    pub fn write_csv(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let mut writer = csv::Writer::from_path(path)?;
+        for entry in self.entries.iter() {
+            let plex = entry.plex.as_ref();
+            let explanation = entry.explanation.as_ref();
+            writer.serialize(CsvRow {
+                position: entry.position,
+                status: entry.status.name(),
+                added: entry.added,
+                spotify_artist: unmatched::artists(&entry.spotify),
+                spotify_title: unmatched::title(&entry.spotify),
+                spotify_album: unmatched::album(&entry.spotify),
+                spotify_uri: entry.spotify.id(),
+                spotify_url: unmatched::spotify(&entry.spotify)
+                    .and_then(|m| m.url.as_deref())
+                    .unwrap_or_default(),
+                plex_artist: plex.map(|p| p.artist.join(", ")).unwrap_or_default(),
+                plex_title: plex.map(|p| p.track.as_str()).unwrap_or_default(),
+                plex_album: plex.map(|p| p.album.as_str()).unwrap_or_default(),
+                plex_rating_key: plex.map(|p| p.id().as_str()).unwrap_or_default(),
+                plex_file: plex.and_then(file).map(|f| f.as_str()).unwrap_or_default(),
+                matcher: explanation.map(|e| e.matcher.as_str()).unwrap_or_default(),
+                method: explanation.map(|e| e.method.as_str()).unwrap_or_default(),
+                score: explanation.map(|e| e.score),
+                transformations: explanation
+                    .map(|e| e.transformations.join(", "))
+                    .unwrap_or_default(),
+            })?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn write_html(&self, path: &Path) -> Result<(), anyhow::Error> {
+        std::fs::write(path, self.html())?;
+        Ok(())
+    }
+
+    fn html(&self) -> String {
+        let mut html = String::new();
+        let title = escape(&self.playlist);
+        let _ = write!(
+            html,
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; width: 100%; }}
+th, td {{ border-bottom: 1px solid #ddd; padding: 4px 8px; text-align: left; vertical-align: top; }}
+td.why {{ color: #666; font-size: 0.85em; }}
+.matched {{ background: #e8f5e9; }}
+.low-confidence {{ background: #fff8e1; }}
+.unmatched {{ background: #ffebee; }}
+.duplicate {{ background: #ede7f6; }}
+.overridden {{ background: #e3f2fd; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p>"#
+        );
+        let statuses = [
+            Status::Matched,
+            Status::LowConfidence,
+            Status::Unmatched,
+            Status::Duplicate,
+            Status::Overridden,
+        ];
+        let summary: Vec<String> = statuses
+            .iter()
+            .map(|s| format!("{} {}", self.count(*s), s.name()))
+            .collect();
+        let _ = write!(
+            html,
+            "{} tracks: {}</p>\n<table>\n<tr><th>#</th><th>Status</th><th>Spotify</th><th>Plex</th><th>Why</th></tr>\n",
+            self.entries.len(),
+            summary.join(", ")
+        );
+        for entry in self.entries.iter() {
+            let plex = match &entry.plex {
+                Some(plex) => {
+                    let added = if entry.added { "" } else { " (not added)" };
+                    format!(
+                        "{}<br><small>{}{}</small>",
+                        escape(&MatchExplanation::describe(plex)),
+                        escape(&plex.album),
+                        added
+                    )
+                }
+                None => String::new(),
+            };
+            let why = match &entry.explanation {
+                Some(explanation) => escape(&explanation.to_string()),
+                None => String::new(),
+            };
+            // the names as spotify shows them, linked to the track on open.spotify.com.
+            let spotify = escape(&format!(
+                "{} - {}",
+                unmatched::artists(&entry.spotify),
+                unmatched::title(&entry.spotify)
+            ));
+            let spotify = match unmatched::spotify(&entry.spotify).and_then(|m| m.url.as_ref()) {
+                Some(url) => format!("<a href=\"{}\">{}</a>", escape(url), spotify),
+                None => spotify,
+            };
+            let _ = writeln!(
+                html,
+                "<tr class=\"{status}\"><td>{position}</td><td>{status}</td><td>{spotify}<br><small>{album}</small></td><td>{plex}</td><td class=\"why\">{why}</td></tr>",
+                status = entry.status.name(),
+                position = entry.position,
+                album = escape(unmatched::album(&entry.spotify)),
+            );
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{plex::matcher::MatchConfig, track_album_artist::SpotifyMetadata};
+
+    fn plex_track(rating_key: &str) -> TrackAlbumArtist {
+        TrackAlbumArtist {
+            track: rating_key.to_string(),
+            ..TrackAlbumArtist::plex(rating_key)
+        }
+    }
+
+    #[test]
+    fn test_status() {
+        // spotify track, pipeline match, match after the assignment, added, and how it was found.
+        let cases = [
+            (
+                "a",
+                Some("1"),
+                Some("1"),
+                true,
+                "isrc",
+                1.0,
+                Status::Matched,
+            ),
+            (
+                "b",
+                Some("2"),
+                Some("2"),
+                true,
+                "levenshtein distance",
+                3.0,
+                Status::LowConfidence,
+            ),
+            (
+                "c",
+                Some("2"),
+                Some("3"),
+                true,
+                "isrc",
+                1.0,
+                Status::Overridden,
+            ),
+            ("d", None, None, false, "", 0.0, Status::Unmatched),
+            // the assignment gave its match to another track.
+            ("f", Some("3"), None, false, "isrc", 1.0, Status::Unmatched),
+            (
+                "e",
+                Some("1"),
+                Some("1"),
+                false,
+                "isrc",
+                1.0,
+                Status::Duplicate,
+            ),
+        ];
+        let spotify_tracks: Vec<TrackAlbumArtist> = cases
+            .iter()
+            .map(|c| TrackAlbumArtist {
+                track: c.0.to_string(),
+                ..TrackAlbumArtist::spotify(c.0)
+            })
+            .collect();
+        let result = |key: Option<&str>, method: &str, score: f64| {
+            key.map(|key| {
+                let explanation =
+                    MatchExplanation::new(method, String::new(), String::new(), score);
+                MatchResult::new(&plex_track(key), explanation)
+            })
+        };
+        let picked: Vec<Option<MatchResult>> =
+            cases.iter().map(|c| result(c.1, c.4, c.5)).collect();
+        let results: Vec<Option<MatchResult>> =
+            cases.iter().map(|c| result(c.2, c.4, c.5)).collect();
+        let added: Vec<bool> = cases.iter().map(|c| c.3).collect();
+        let duplicates =
+            crate::plex::matcher::duplicates::find_duplicates(&spotify_tracks, &results);
+        let config = MatchConfig::default();
+        let plex_tracks = PlexIndex::new(vec![plex_track("1"), plex_track("2")], &config);
+
+        let report = Report::new(
+            "test",
+            &spotify_tracks,
+            &picked,
+            &results,
+            &duplicates,
+            &added,
+            &plex_tracks,
+        );
+        for (entry, case) in report.entries.iter().zip(cases.iter()) {
+            assert_eq!(entry.status, case.6, "{}", case.0);
+        }
+        assert!(report.html().contains("1 low-confidence"));
+
+        // the names as spotify shows them and the link to open it.
+        let spotify_track = TrackAlbumArtist {
+            track: "hurt".to_string(),
+            artist: vec!["johnny cash".to_string()],
+            metadata: MetadataType::Spotify(SpotifyMetadata {
+                uri: "https://api.spotify.com/v1/tracks/28cnXtME493VX9NOw9cIUh".to_string(),
+                name: "Hurt".to_string(),
+                artists: vec!["Johnny Cash".to_string()],
+                album: "American IV: The Man Comes Around".to_string(),
+                url: Some("https://open.spotify.com/track/28cnXtME493VX9NOw9cIUh".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let html = Report::new(
+            "test",
+            &[spotify_track],
+            &[None],
+            &[None],
+            &[Duplicate::No],
+            &[false],
+            &plex_tracks,
+        )
+        .html();
+        assert!(html.contains(
+            "<a href=\"https://open.spotify.com/track/28cnXtME493VX9NOw9cIUh\">Johnny Cash - Hurt</a>"
+        ));
+        assert!(html.contains("American IV: The Man Comes Around"));
+        assert!(!html.contains("api.spotify.com"));
+    }
+}
@@ -1,18 +1,48 @@
+use std::collections::HashMap;
+
 use rspotify::{
     model::{FullArtist, FullTrack, PlayableItem, PlaylistId, SimplifiedArtist},
     prelude::{BaseClient, OAuthClient},
     scopes, AuthCodeSpotify, Credentials, OAuth,
 };
+use serde::Deserialize;
 
-use crate::track_album_artist::{MetadataType, SpotifyMetadata, TrackAlbumArtist};
+use crate::{
+    artist_credit::{ArtistCredit, ArtistCreditParser},
+    logging::Progress,
+    normalize::normalize,
+    track_album_artist::{MetadataType, SpotifyMetadata, TrackAlbumArtist},
+    track_version::parse_version,
+};
 
-pub async fn get_spotify_tracks(
-    client_id: String,
-    secret_token: String,
-    playlist_id: String,
-) -> Result<Vec<TrackAlbumArtist>, anyhow::Error> {
-    let mut tracks: Vec<TrackAlbumArtist> = Vec::new();
-    let creds = Credentials::new(&client_id, &secret_token);
+/// Spotify lists every credited artist on the track, the ones also named in a
+/// "(feat. ...)" of the title are the featured ones.
+fn get_artist_credit(artists: Vec<String>, title_featured: Vec<String>) -> ArtistCredit {
+    let (mut featured, mut primary): (Vec<String>, Vec<String>) = artists
+        .into_iter()
+        .partition(|a| title_featured.contains(a));
+    if primary.is_empty() && !featured.is_empty() {
+        primary.push(featured.remove(0));
+    }
+    let mut credit = ArtistCredit { primary, featured };
+    credit.merge(ArtistCredit {
+        primary: vec![],
+        featured: title_featured,
+    });
+    credit
+}
+
+/// Release dates are `YYYY`, `YYYY-MM` or `YYYY-MM-DD` depending on their precision.
+fn year(release_date: &str) -> Option<u32> {
+    release_date.get(..4)?.parse().ok()
+}
+
+/// Authorizes once, the token is cached and refreshed for the later requests.
+pub fn spotify_client(
+    client_id: &str,
+    secret_token: &str,
+) -> Result<AuthCodeSpotify, anyhow::Error> {
+    let creds = Credentials::new(client_id, secret_token);
     let oauth = OAuth {
         redirect_uri: "http://localhost:8888/callback".to_string(),
         scopes: scopes!("playlist-read-private"),
@@ -24,48 +54,98 @@ pub async fn get_spotify_tracks(
 
     let url = spotify.get_authorize_url(false).unwrap();
     spotify.prompt_for_token(&url)?;
+    Ok(spotify)
+}
 
+#[derive(Deserialize)]
+struct Snapshot {
+    snapshot_id: String,
+}
+
+/// Changes whenever the playlist does, a single small request instead of all its items.
+pub fn get_snapshot_id(
+    spotify: &AuthCodeSpotify,
+    playlist_id: &str,
+) -> Result<String, anyhow::Error> {
+    let query = HashMap::from([("fields", "snapshot_id")]);
+    let response = spotify.api_get(&format!("playlists/{}", playlist_id), &query)?;
+    let snapshot: Snapshot = serde_json::from_str(&response)?;
+    Ok(snapshot.snapshot_id)
+}
+
+pub async fn get_spotify_tracks(
+    spotify: &AuthCodeSpotify,
+    playlist_id: &str,
+) -> Result<Vec<TrackAlbumArtist>, anyhow::Error> {
+    let mut tracks: Vec<TrackAlbumArtist> = Vec::new();
     let stream = spotify.playlist_items(PlaylistId::from_id(playlist_id).unwrap(), None, None);
 
     let playable: Vec<PlayableItem> = stream.map(|item| item.unwrap().track.unwrap()).collect();
-    let mut i = 1;
+    let parser = ArtistCreditParser::default();
+    let mut progress = Progress::new("Processing Spotify tracks", playable.len() as u64);
     // map to TrackAlbumArtist
     playable.iter().for_each(|p| {
         match p {
             PlayableItem::Track(track) => {
+                let (title, featured) = parser.parse_title(&track.name);
+                let (title, version) = parse_version(&title);
+                let credit = get_artist_credit(
+                    track.artists.iter().map(|a| normalize(&a.name)).collect(),
+                    featured,
+                );
                 let track_album_artist = TrackAlbumArtist {
-                    track: track.name.to_lowercase().clone(),
-                    album: track.album.name.to_lowercase().clone(),
-                    artist: track
-                        .artists
-                        .iter()
-                        .map(|a| a.name.trim().to_lowercase().clone())
-                        .collect(),
+                    track: title,
+                    version,
+                    album: normalize(&track.album.name),
+                    artist: credit.primary,
+                    featured_artist: credit.featured,
+                    duration_ms: u64::try_from(track.duration.num_milliseconds()).ok(),
+                    track_number: Some(track.track_number),
+                    disc_number: u32::try_from(track.disc_number).ok(),
+                    isrc: track
+                        .external_ids
+                        .get("isrc")
+                        .map(|i| i.trim().to_uppercase()),
+                    musicbrainz_ids: vec![],
                     metadata: MetadataType::Spotify(SpotifyMetadata {
                         uri: track.href.as_ref().unwrap().clone(),
+                        name: track.name.clone(),
+                        artists: track.artists.iter().map(|a| a.name.clone()).collect(),
+                        album: track.album.name.clone(),
+                        url: track.external_urls.get("spotify").cloned(),
+                        album_url: track.album.external_urls.get("spotify").cloned(),
+                        release_year: track.album.release_date.as_deref().and_then(year),
                     }),
                 };
                 tracks.push(track_album_artist.clone());
             }
             PlayableItem::Episode(episode) => {
                 let track_album_artist = TrackAlbumArtist {
-                    track: episode.name.to_lowercase().clone(),
-                    album: episode.show.name.to_lowercase().clone(),
-                    artist: vec![episode.show.publisher.to_lowercase().clone()],
+                    track: normalize(&episode.name),
+                    version: None,
+                    album: normalize(&episode.show.name),
+                    artist: vec![normalize(&episode.show.publisher)],
+                    featured_artist: vec![],
+                    duration_ms: u64::try_from(episode.duration.num_milliseconds()).ok(),
+                    track_number: None,
+                    disc_number: None,
+                    isrc: None,
+                    musicbrainz_ids: vec![],
                     metadata: MetadataType::Spotify(SpotifyMetadata {
                         uri: episode.href.clone(),
+                        name: episode.name.clone(),
+                        artists: vec![episode.show.publisher.clone()],
+                        album: episode.show.name.clone(),
+                        url: episode.external_urls.get("spotify").cloned(),
+                        album_url: episode.show.external_urls.get("spotify").cloned(),
+                        release_year: year(&episode.release_date),
                     }),
                 };
                 tracks.push(track_album_artist.clone());
             }
         }
-        print!(
-            "\rProcessing Spotify {} of {}               ",
-            i,
-            playable.len()
-        );
-        i += 1;
+        progress.inc(String::new());
     });
-    println!("");
+    progress.finish();
     Ok(tracks)
 }
@@ -0,0 +1,127 @@
+use std::{collections::HashMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+/// What a playlist was last synced from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlaylistState {
+    pub snapshot_id: String,
+    pub library_version: Option<i64>,
+    /// The plex playlist it was synced to, empty in state files from before it was kept.
+    #[serde(default)]
+    pub plex_playlist_id: String,
+}
+
+/// Persisted between runs so that playlists that didn't change aren't synced again.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct State {
+    /// By spotify playlist id.
+    #[serde(default)]
+    pub playlists: HashMap<String, PlaylistState>,
+}
+
+impl State {
+    /// An empty state when the file doesn't exist yet.
+    pub fn load(path: &str) -> Result<Self, anyhow::Error> {
+        match std::fs::File::open(path) {
+            Ok(file) => Ok(serde_json::from_reader(file)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Written next to the file and renamed over it, so an interrupted run can't leave
+    /// it half written.
+    pub fn save(&self, path: &str) -> Result<(), anyhow::Error> {
+        let tmp = Path::new(path).with_extension("tmp");
+        serde_json::to_writer_pretty(std::fs::File::create(&tmp)?, self)?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// Whether neither the playlist nor the library changed since its last sync. A
+    /// library without a version may have changed.
+    pub fn is_synced(
+        &self,
+        playlist_id: &str,
+        snapshot_id: &str,
+        library_version: Option<i64>,
+    ) -> bool {
+        library_version.is_some()
+            && self.playlists.get(playlist_id).is_some_and(|synced| {
+                synced.snapshot_id == snapshot_id && synced.library_version == library_version
+            })
+    }
+
+    /// The plex playlist the playlist was last synced to, empty when there is none.
+    pub fn plex_playlist_id(&self, playlist_id: &str) -> String {
+        self.playlists
+            .get(playlist_id)
+            .map(|synced| synced.plex_playlist_id.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn synced(
+        &mut self,
+        playlist_id: &str,
+        snapshot_id: &str,
+        library_version: Option<i64>,
+        plex_playlist_id: &str,
+    ) {
+        self.playlists.insert(
+            playlist_id.to_string(),
+            PlaylistState {
+                snapshot_id: snapshot_id.to_string(),
+                library_version,
+                plex_playlist_id: plex_playlist_id.to_string(),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_synced() {
+        let mut state = State::default();
+        assert!(!state.is_synced("playlist", "a", Some(1)));
+        state.synced("playlist", "a", Some(1), "10");
+        state.synced("unversioned", "a", None, "11");
+
+        let cases = [
+            ("playlist", "a", Some(1), true),
+            ("playlist", "b", Some(1), false),
+            ("playlist", "a", Some(2), false),
+            ("playlist", "a", None, false),
+            ("unversioned", "a", None, false),
+            ("other", "a", Some(1), false),
+        ];
+        for (playlist_id, snapshot_id, library_version, expected) in cases {
+            assert_eq!(
+                state.is_synced(playlist_id, snapshot_id, library_version),
+                expected,
+                "{} {} {:?}",
+                playlist_id,
+                snapshot_id,
+                library_version
+            );
+        }
+    }
+
+    #[test]
+    fn test_plex_playlist_id() {
+        let mut state: State = serde_json::from_str(
+            r#"{"playlists": {"old": {"snapshot_id": "a", "library_version": 1}}}"#,
+        )
+        .unwrap();
+        assert!(state.is_synced("old", "a", Some(1)));
+        assert_eq!(state.plex_playlist_id("old"), "");
+        assert_eq!(state.plex_playlist_id("other"), "");
+
+        state.synced("old", "b", Some(1), "10");
+        let state: State = serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+        assert_eq!(state.plex_playlist_id("old"), "10");
+    }
+}
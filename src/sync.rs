@@ -0,0 +1,262 @@
+use std::collections::{BTreeMap, HashSet};
+
+use log::{info, warn};
+
+use crate::{
+    plex::{
+        client::Plex,
+        matcher::{
+            assignment,
+            duplicates::{apply_policy, find_duplicates, Duplicate, DuplicatePolicy},
+            index::PlexIndex,
+            pipeline::Pipeline,
+            MatchConfig, MatchResult,
+        },
+        playlist, refresh_library, PlexLibrary,
+    },
+    report::{file_name, Report},
+    track_album_artist::TrackAlbumArtist,
+    unmatched::Unmatched,
+};
+
+/// How the matches are turned into the playlist and reported.
+pub struct SyncOptions {
+    pub explain: bool,
+    pub one_to_one: bool,
+    pub duplicates: DuplicatePolicy,
+    pub report_dir: Option<String>,
+    pub unmatched_dir: Option<String>,
+    /// Shell command downloading the unmatched tracks, given the file of their spotify
+    /// links as `$1`.
+    pub download_command: Option<String>,
+    /// Folder of the music library plex only scans after a download, all of it when unset.
+    pub refresh_path: Option<String>,
+}
+
+/// The plex playlist a spotify playlist is synced to, created with its first track
+/// when there is no id yet.
+pub struct PlexPlaylist {
+    pub name: String,
+    pub id: String,
+}
+
+impl PlexPlaylist {
+    /// Empties the playlist synced before so that its tracks are replaced instead of
+    /// creating another one. Forgets its id when it was deleted in plex, so it is
+    /// created again.
+    pub async fn clear(&mut self, plex: &Plex) -> Result<(), anyhow::Error> {
+        if self.id.is_empty() {
+            return Ok(());
+        }
+        match plex.clear_playlist(&self.id).await {
+            Err(err)
+                if err
+                    .downcast_ref::<reqwest::Error>()
+                    .and_then(|e| e.status())
+                    == Some(reqwest::StatusCode::NOT_FOUND) =>
+            {
+                warn!("{} was deleted in plex, creating it again", self.name);
+                self.id.clear();
+                Ok(())
+            }
+            result => result,
+        }
+    }
+}
+
+/// Matches every spotify track, replaces the tracks of the plex playlist with the
+/// matches and writes the reports asked for. The playlist is only emptied once every
+/// track was matched.
+pub async fn find_matches_and_update_playlist(
+    plex: &Plex,
+    spotify_tracks: &[TrackAlbumArtist],
+    plex_tracks: &PlexIndex,
+    plex_playlist: &mut PlexPlaylist,
+    config: &MatchConfig,
+    pipeline: &Pipeline,
+    options: &SyncOptions,
+) -> Result<Report, anyhow::Error> {
+    let mut results = vec![];
+    for spotify_track in spotify_tracks.iter() {
+        results.push(pipeline.run(plex_tracks, spotify_track, config).await);
+    }
+    // kept to tell which matches the assignment changed.
+    let picked = results.clone();
+    if options.one_to_one {
+        results = assignment::one_to_one(spotify_tracks, &results, plex_tracks, config);
+    }
+
+    // decided before writing anything, the first of several matches may be skipped too.
+    let duplicates = find_duplicates(spotify_tracks, &results);
+    let keep = apply_policy(&duplicates, options.duplicates);
+    plex_playlist.clear(plex).await?;
+
+    for (i, (spotify_track, result)) in spotify_tracks.iter().zip(results.iter()).enumerate() {
+        if let Some(MatchResult {
+            track: track_result,
+            explanation,
+        }) = result
+        {
+            if options.explain {
+                println!("{}", explanation);
+            }
+            if let Duplicate::Collision { first } = duplicates[i] {
+                let matched = results[first].as_ref().unwrap();
+                warn!(
+                    "Found a duplicate result: ([{:?} - {}] already matched [{:?} - {}]) => new match: {:?} - {}",
+                    spotify_tracks[first].artist, spotify_tracks[first].track, matched.track.artist, matched.track.track, track_result.artist, track_result.track
+                );
+            }
+            if !keep[i] {
+                info!(
+                    "Skipped duplicate: {:?} - {} => {:?} - {}",
+                    spotify_track.artist,
+                    spotify_track.track,
+                    track_result.artist,
+                    track_result.track
+                );
+                continue;
+            }
+            playlist(
+                &mut plex_playlist.id,
+                plex,
+                track_result,
+                &plex_playlist.name,
+            )
+            .await?;
+            continue;
+        }
+        warn!(
+            "No match found for: {:?} - {}",
+            spotify_track.artist, spotify_track.track
+        );
+        let mut immediate_dupe_list = BTreeMap::new();
+        for plex_track in plex_tracks.tracks().iter() {
+            if plex_track.artist == spotify_track.artist || plex_track.album == spotify_track.album
+            {
+                let key = format!("{:?}{}", plex_track.artist, plex_track.album);
+                if immediate_dupe_list.contains_key(&key) {
+                    continue;
+                }
+                immediate_dupe_list.insert(key, plex_track);
+                info!(
+                    "Match from the artist/album: {:?} - {}",
+                    plex_track.artist, plex_track.track
+                );
+            }
+        }
+    }
+
+    let report = Report::new(
+        &plex_playlist.name,
+        spotify_tracks,
+        &picked,
+        &results,
+        &duplicates,
+        &keep,
+        plex_tracks,
+    );
+    if let Some(dir) = &options.report_dir {
+        report.write(dir)?;
+    }
+    if let Some(dir) = &options.unmatched_dir {
+        let unmatched = Unmatched::from_report(&report);
+        if !unmatched.is_empty() {
+            unmatched.write(dir, &plex_playlist.name)?;
+        }
+    }
+    Ok(report)
+}
+
+/// Matches the tracks again against a library with new tracks and adds the ones found at
+/// the end of the playlist. The ones added are removed from `unmatched`, also when adding
+/// a later one fails, so that they can be tried again without adding any twice.
+pub async fn add_new_matches(
+    plex: &Plex,
+    plex_tracks: &PlexIndex,
+    unmatched: &mut Vec<TrackAlbumArtist>,
+    plex_playlist: &mut PlexPlaylist,
+    config: &MatchConfig,
+    pipeline: &Pipeline,
+) -> Result<(), anyhow::Error> {
+    let mut i = 0;
+    while i < unmatched.len() {
+        match pipeline.run(plex_tracks, &unmatched[i], config).await {
+            Some(result) => {
+                info!(
+                    "Found in the new tracks for {}: {}",
+                    plex_playlist.name, result.explanation
+                );
+                playlist(
+                    &mut plex_playlist.id,
+                    plex,
+                    &result.track,
+                    &plex_playlist.name,
+                )
+                .await?;
+                unmatched.remove(i);
+            }
+            None => i += 1,
+        }
+    }
+    Ok(())
+}
+
+/// Runs the download command on the unmatched tracks, waits for plex to scan the
+/// downloads and scans their artists again to add the tracks found to the playlist.
+/// Returns the ones still unmatched.
+pub async fn download_unmatched(
+    plex: &Plex,
+    library: &mut PlexLibrary,
+    mut unmatched: Vec<TrackAlbumArtist>,
+    plex_playlist: &mut PlexPlaylist,
+    config: &MatchConfig,
+    pipeline: &Pipeline,
+    options: &SyncOptions,
+) -> Result<Vec<TrackAlbumArtist>, anyhow::Error> {
+    let Some(command) = &options.download_command else {
+        return Ok(unmatched);
+    };
+    if unmatched.is_empty() {
+        return Ok(unmatched);
+    }
+
+    let links =
+        std::env::temp_dir().join(format!("{}.download.txt", file_name(&plex_playlist.name)));
+    Unmatched::new(&unmatched).write_urls(&links)?;
+    info!(
+        "Downloading {} unmatched tracks of {}",
+        unmatched.len(),
+        plex_playlist.name
+    );
+    let status = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .arg("sh")
+        .arg(&links)
+        .status()
+        .await?;
+    // some tracks may have been downloaded all the same.
+    if !status.success() {
+        warn!("The download command failed: {}", status);
+    }
+
+    refresh_library(plex, options.refresh_path.as_deref()).await?;
+    let artists: HashSet<String> = unmatched
+        .iter()
+        .flat_map(|t| t.artist.iter().chain(t.featured_artist.iter()))
+        .cloned()
+        .collect();
+    library.rescan_artists_named(plex, &artists).await?;
+    let plex_tracks = PlexIndex::new(library.tracks(), config);
+    add_new_matches(
+        plex,
+        &plex_tracks,
+        &mut unmatched,
+        plex_playlist,
+        config,
+        pipeline,
+    )
+    .await?;
+    Ok(unmatched)
+}
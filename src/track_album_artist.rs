@@ -1,28 +1,117 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+use crate::track_version::TrackVersion;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TrackAlbumArtist {
+    /// Title without the featured artists and the version.
     pub track: String,
+    pub version: Option<TrackVersion>,
     pub album: String,
+    /// Primary artists.
     pub artist: Vec<String>,
+    /// Guests credited with "feat.", "with" and the like, in the artist field or the title.
+    /// Compared as well, libraries sometimes file a track under one of them.
+    #[serde(default)]
+    pub featured_artist: Vec<String>,
+    pub duration_ms: Option<u64>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub isrc: Option<String>,
+    /// MusicBrainz recording ids, only known for plex tracks tagged with them.
+    #[serde(default)]
+    pub musicbrainz_ids: Vec<String>,
     pub metadata: MetadataType,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+impl TrackAlbumArtist {
+    /// Identifies the track on its side: the plex rating key or the spotify url.
+    pub fn id(&self) -> &String {
+        match &self.metadata {
+            MetadataType::Plex(meta) => &meta.rating_key,
+            MetadataType::Spotify(meta) => &meta.uri,
+        }
+    }
+
+    /// Every artist the track could be filed under, the primary ones first.
+    pub fn credited_artists(&self) -> impl Iterator<Item = &String> {
+        self.artist.iter().chain(self.featured_artist.iter())
+    }
+
+    /// An otherwise empty plex track, for tests to fill in with struct update syntax.
+    #[cfg(test)]
+    pub fn plex(rating_key: &str) -> Self {
+        Self {
+            metadata: MetadataType::Plex(PlexMetadata {
+                rating_key: rating_key.to_string(),
+                key: format!("/library/metadata/{}", rating_key),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// An otherwise empty spotify track, for tests to fill in with struct update syntax.
+    #[cfg(test)]
+    pub fn spotify(uri: &str) -> Self {
+        Self {
+            metadata: MetadataType::Spotify(SpotifyMetadata {
+                uri: uri.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PlexMetadata {
     pub machine_identifier: String,
     pub provider_identifier: String,
     pub rating_key: String,
     pub key: String,
+    /// The files of the track, several when the library has it in several formats.
+    #[serde(default)]
+    pub media: Vec<MediaInfo>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaInfo {
+    pub codec: Option<String>,
+    /// In kbps.
+    pub bitrate: Option<u32>,
+    pub channels: Option<u32>,
+    pub file: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SpotifyMetadata {
     pub uri: String,
+    /// Title, artists and album as spotify shows them, not normalized.
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub artists: Vec<String>,
+    #[serde(default)]
+    pub album: String,
+    /// The open.spotify.com links of the track and its album.
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub album_url: Option<String>,
+    #[serde(default)]
+    pub release_year: Option<u32>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MetadataType {
     Plex(PlexMetadata),
     Spotify(SpotifyMetadata),
 }
+
+/// Plex tracks are the ones built in most places.
+impl Default for MetadataType {
+    fn default() -> Self {
+        Self::Plex(PlexMetadata::default())
+    }
+}
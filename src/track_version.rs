@@ -0,0 +1,216 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// "(club remix)" or "[live]" at the end of a title.
+static BRACKETED_SUFFIX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s*[\(\[]([^\(\)\[\]]+)[\)\]]\s*$").unwrap());
+/// "- live at wembley" or "- 2011 remaster", the way spotify titles versions.
+static DASHED_SUFFIX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s+-\s+([^-]+)$").unwrap());
+
+/// Suffixes that name the usual version of a track, they are removed without a version.
+const ORIGINAL_VERSIONS: [&str; 4] = [
+    "album version",
+    "original version",
+    "original mix",
+    "single version",
+];
+
+/// Words describing the kind of version, what remains is the qualifier.
+const VERSION_WORDS: [&str; 14] = [
+    "remastered",
+    "remaster",
+    "radio",
+    "edit",
+    "instrumental",
+    "acoustic",
+    "unplugged",
+    "live",
+    "extended",
+    "remix",
+    "mix",
+    "rmx",
+    "demo",
+    "version",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VersionKind {
+    Remaster,
+    RadioEdit,
+    Instrumental,
+    Acoustic,
+    Live,
+    Extended,
+    Remix,
+    Demo,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackVersion {
+    pub kind: VersionKind,
+    /// e.g. "club" for a club remix, "2011" for a 2011 remaster.
+    pub qualifier: Option<String>,
+}
+
+fn has_word(text: &str, word: &str) -> bool {
+    text.split(|c: char| !c.is_alphanumeric())
+        .any(|w| w == word)
+}
+
+fn version_kind(text: &str) -> Option<VersionKind> {
+    if text.contains("remaster") {
+        Some(VersionKind::Remaster)
+    } else if text.contains("radio edit")
+        || text.contains("radio version")
+        || has_word(text, "edit")
+    {
+        Some(VersionKind::RadioEdit)
+    } else if has_word(text, "instrumental") {
+        Some(VersionKind::Instrumental)
+    } else if has_word(text, "acoustic") || has_word(text, "unplugged") {
+        Some(VersionKind::Acoustic)
+    } else if has_word(text, "live") {
+        Some(VersionKind::Live)
+    } else if has_word(text, "extended") {
+        Some(VersionKind::Extended)
+    } else if has_word(text, "remix") || has_word(text, "mix") || has_word(text, "rmx") {
+        Some(VersionKind::Remix)
+    } else if has_word(text, "demo") {
+        Some(VersionKind::Demo)
+    } else {
+        None
+    }
+}
+
+fn parse_section(text: &str) -> Option<Option<TrackVersion>> {
+    let text = text.trim();
+    if ORIGINAL_VERSIONS.contains(&text) {
+        return Some(None);
+    }
+    let kind = version_kind(text)?;
+    let qualifier = text
+        .split_whitespace()
+        .filter(|w| !VERSION_WORDS.contains(w))
+        .collect::<Vec<&str>>()
+        .join(" ");
+    Some(Some(TrackVersion {
+        kind,
+        qualifier: (!qualifier.is_empty()).then_some(qualifier),
+    }))
+}
+
+/// Splits the version off a normalized title, "song (club remix)" and "song - club remix"
+/// both become ("song", remix "club"). Sections that don't describe a version, like
+/// "(interlude)", stay in the title.
+pub fn parse_version(title: &str) -> (String, Option<TrackVersion>) {
+    let mut title = title.trim().to_string();
+    let mut version: Option<TrackVersion> = None;
+    while let Some(captures) = BRACKETED_SUFFIX
+        .captures(&title)
+        .or_else(|| DASHED_SUFFIX.captures(&title))
+    {
+        let (Some(section), Some(text)) = (captures.get(0), captures.get(1)) else {
+            break;
+        };
+        let Some(parsed) = parse_section(text.as_str()) else {
+            break;
+        };
+        // "song - live (remastered)" is a live version more than it is a remaster.
+        if version.is_none() || version.as_ref().map(|v| v.kind) == Some(VersionKind::Remaster) {
+            version = parsed.or(version);
+        }
+        let base = title[..section.start()].trim().to_string();
+        if base.is_empty() {
+            break;
+        }
+        title = base;
+    }
+    (title, version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Case<'a> = (&'a str, &'a str, Option<(VersionKind, Option<&'a str>)>);
+
+    #[test]
+    fn test_parse_version() {
+        let cases: [Case; 14] = [
+            ("song", "song", None),
+            (
+                "song (club remix)",
+                "song",
+                Some((VersionKind::Remix, Some("club"))),
+            ),
+            (
+                "song - club remix",
+                "song",
+                Some((VersionKind::Remix, Some("club"))),
+            ),
+            (
+                "song (acoustic)",
+                "song",
+                Some((VersionKind::Acoustic, None)),
+            ),
+            (
+                "song - acoustic version",
+                "song",
+                Some((VersionKind::Acoustic, None)),
+            ),
+            (
+                "song - remastered 2011",
+                "song",
+                Some((VersionKind::Remaster, Some("2011"))),
+            ),
+            (
+                "song - 2011 remaster",
+                "song",
+                Some((VersionKind::Remaster, Some("2011"))),
+            ),
+            (
+                "song - live at wembley",
+                "song",
+                Some((VersionKind::Live, Some("at wembley"))),
+            ),
+            (
+                "song [radio edit]",
+                "song",
+                Some((VersionKind::RadioEdit, None)),
+            ),
+            (
+                "song (instrumental)",
+                "song",
+                Some((VersionKind::Instrumental, None)),
+            ),
+            (
+                "song (extended mix)",
+                "song",
+                Some((VersionKind::Extended, None)),
+            ),
+            (
+                "song - live (remastered)",
+                "song",
+                Some((VersionKind::Live, None)),
+            ),
+            ("song (original mix)", "song", None),
+            ("song (interlude)", "song (interlude)", None),
+        ];
+        for (input, title, version) in cases {
+            let expected = version.map(|(kind, qualifier)| TrackVersion {
+                kind,
+                qualifier: qualifier.map(|q| q.to_string()),
+            });
+            assert_eq!(
+                parse_version(input),
+                (title.to_string(), expected),
+                "{:?}",
+                input
+            );
+        }
+        assert_eq!(parse_version("live").0, "live");
+        assert_eq!(parse_version("(remix)").0, "(remix)");
+    }
+}
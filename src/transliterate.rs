@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use deunicode::deunicode_char;
+
+/// Modern greek pairs that are romanized together, checked before single letters.
+const GREEK_DIGRAPHS: [(&str, &str); 8] = [
+    ("γγ", "ng"),
+    ("γκ", "g"),
+    ("γχ", "nch"),
+    ("μπ", "b"),
+    ("ντ", "d"),
+    ("ου", "ou"),
+    ("αι", "ai"),
+    ("ει", "ei"),
+];
+
+const GREEK_LETTERS: [(char, &str); 25] = [
+    ('α', "a"),
+    ('β', "v"),
+    ('γ', "g"),
+    ('δ', "d"),
+    ('ε', "e"),
+    ('ζ', "z"),
+    ('η', "i"),
+    ('θ', "th"),
+    ('ι', "i"),
+    ('κ', "k"),
+    ('λ', "l"),
+    ('μ', "m"),
+    ('ν', "n"),
+    ('ξ', "x"),
+    ('ο', "o"),
+    ('π', "p"),
+    ('ρ', "r"),
+    ('σ', "s"),
+    ('ς', "s"),
+    ('τ', "t"),
+    ('υ', "y"),
+    ('φ', "f"),
+    ('χ', "ch"),
+    ('ψ', "ps"),
+    ('ω', "o"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Cyrillic,
+    Greek,
+    Hangul,
+    Kana,
+    Han,
+}
+
+fn script(c: char) -> Option<Script> {
+    match c {
+        '\u{0400}'..='\u{052f}' => Some(Script::Cyrillic),
+        '\u{0370}'..='\u{03ff}' | '\u{1f00}'..='\u{1fff}' => Some(Script::Greek),
+        '\u{1100}'..='\u{11ff}' | '\u{3130}'..='\u{318f}' | '\u{ac00}'..='\u{d7af}' => {
+            Some(Script::Hangul)
+        }
+        '\u{3040}'..='\u{30ff}' | '\u{31f0}'..='\u{31ff}' => Some(Script::Kana),
+        '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{f900}'..='\u{faff}' => {
+            Some(Script::Han)
+        }
+        _ => None,
+    }
+}
+
+/// Whether the string has any characters [`romanize`] would transliterate.
+pub fn has_non_latin(s: &str) -> bool {
+    s.chars().any(|c| script(c).is_some())
+}
+
+fn romanize_greek(chars: &[char], i: usize) -> (String, usize) {
+    if let Some(next) = chars.get(i + 1) {
+        let pair: String = [chars[i], *next].iter().collect();
+        if let Some((_, to)) = GREEK_DIGRAPHS.iter().find(|(from, _)| *from == pair) {
+            return (to.to_string(), 2);
+        }
+    }
+    match GREEK_LETTERS.iter().find(|(from, _)| *from == chars[i]) {
+        Some((_, to)) => (to.to_string(), 1),
+        None => (deunicode_char(chars[i]).unwrap_or("").to_string(), 1),
+    }
+}
+
+/// Romanizes cyrillic, greek, hangul, kana and han characters of an already
+/// [normalized](crate::normalize::normalize) string, anything else is kept as is.
+/// Whole strings found in `table` are replaced by their entry instead, which is how
+/// names with a reading the offline tables can't know (e.g. japanese names written
+/// in kanji) get romanized.
+pub fn romanize(s: &str, table: &HashMap<String, String>) -> String {
+    if let Some(romanized) = table.get(s) {
+        return romanized.clone();
+    }
+
+    let chars: Vec<char> = s.chars().collect();
+    let mut romanized = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match script(c) {
+            None => {
+                romanized.push(c);
+                i += 1;
+            }
+            Some(Script::Greek) => {
+                let (to, consumed) = romanize_greek(&chars, i);
+                romanized.push_str(&to);
+                i += consumed;
+            }
+            // the small tsu doubles the consonant that follows, e.g. がっこう => gakkou.
+            Some(Script::Kana) if c == 'っ' || c == 'ッ' => {
+                let next = chars.get(i + 1).and_then(|n| deunicode_char(*n));
+                if let Some(consonant) = next.and_then(|n| n.chars().next()) {
+                    romanized.push(consonant);
+                }
+                i += 1;
+            }
+            // the prolonged sound mark repeats the previous vowel.
+            Some(Script::Kana) if c == 'ー' => {
+                if let Some(vowel) = romanized.chars().last().filter(|v| "aeiou".contains(*v)) {
+                    romanized.push(vowel);
+                }
+                i += 1;
+            }
+            Some(script) => {
+                let to = deunicode_char(c).unwrap_or("").to_lowercase();
+                romanized.push_str(if script == Script::Han {
+                    &to
+                } else {
+                    to.trim_end()
+                });
+                i += 1;
+            }
+        }
+    }
+    romanized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_romanize() {
+        let cases = [
+            ("кино", "kino"),
+            ("виктор цой", "viktor tsoi"),
+            ("βαγγελης", "vangelis"),
+            ("μπουζουκι", "bouzouki"),
+            ("방탄소년단", "bangtansonyeondan"),
+            ("아이유", "aiyu"),
+            ("ヨアソビ", "yoasobi"),
+            ("がっこう", "gakkou"),
+            ("ラーメン", "raamen"),
+            ("ryuichi sakamoto", "ryuichi sakamoto"),
+        ];
+        let table = HashMap::new();
+        for (input, expected) in cases {
+            assert_eq!(romanize(input, &table), expected, "romanize({:?})", input);
+        }
+    }
+
+    #[test]
+    fn test_romanize_with_table() {
+        let table = HashMap::from([("坂本龍一".to_string(), "ryuichi sakamoto".to_string())]);
+        assert_eq!(romanize("坂本龍一", &table), "ryuichi sakamoto");
+        assert_eq!(romanize("龍", &table).trim(), "long");
+    }
+}
@@ -0,0 +1,220 @@
+use std::{collections::HashSet, io::Write, path::Path};
+
+use serde::Serialize;
+
+use crate::{
+    report::{file_name, Report, Status},
+    track_album_artist::{MetadataType, SpotifyMetadata, TrackAlbumArtist},
+};
+
+/// An album with the playlist's tracks from it that the library doesn't have.
+pub struct Album<'a> {
+    pub artist: String,
+    pub name: String,
+    pub release_year: Option<u32>,
+    pub url: Option<String>,
+    pub tracks: Vec<&'a TrackAlbumArtist>,
+}
+
+/// The unmatched spotify tracks grouped by album, to know what to buy or rip.
+pub struct Unmatched<'a> {
+    pub albums: Vec<Album<'a>>,
+}
+
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    album_artist: &'a str,
+    album: &'a str,
+    release_year: Option<u32>,
+    album_url: Option<&'a str>,
+    disc_number: Option<u32>,
+    track_number: Option<u32>,
+    artist: String,
+    title: &'a str,
+    isrc: Option<&'a str>,
+    url: Option<&'a str>,
+}
+
+pub(crate) fn spotify(track: &TrackAlbumArtist) -> Option<&SpotifyMetadata> {
+    match &track.metadata {
+        MetadataType::Spotify(meta) => Some(meta),
+        MetadataType::Plex(_) => None,
+    }
+}
+
+/// The names as spotify shows them, the normalized ones for dumps that don't have them.
+pub(crate) fn artists(track: &TrackAlbumArtist) -> String {
+    match spotify(track) {
+        Some(meta) if !meta.artists.is_empty() => meta.artists.join(", "),
+        _ => track.artist.join(", "),
+    }
+}
+
+pub(crate) fn title(track: &TrackAlbumArtist) -> &str {
+    match spotify(track) {
+        Some(meta) if !meta.name.is_empty() => &meta.name,
+        _ => &track.track,
+    }
+}
+
+pub(crate) fn album(track: &TrackAlbumArtist) -> &str {
+    match spotify(track) {
+        Some(meta) if !meta.album.is_empty() => &meta.album,
+        _ => &track.album,
+    }
+}
+
+impl<'a> Unmatched<'a> {
+    /// Albums in the order they first appear, their tracks in album order. Tracks that
+    /// are in the playlist several times are listed once.
+    pub fn new(tracks: impl IntoIterator<Item = &'a TrackAlbumArtist>) -> Self {
+        let mut albums: Vec<Album<'a>> = vec![];
+        let mut seen = HashSet::new();
+        for track in tracks {
+            if !seen.insert(track.id()) {
+                continue;
+            }
+            let meta = spotify(track);
+            let url = meta.and_then(|m| m.album_url.clone());
+            let artist = match meta {
+                Some(meta) if !meta.artists.is_empty() => meta.artists[0].clone(),
+                _ => track.artist.first().cloned().unwrap_or_default(),
+            };
+            let name = album(track).to_string();
+            // without a link, the same album name by the same artist is the same album.
+            let position = albums.iter().position(|a| match (&a.url, &url) {
+                (Some(a), Some(b)) => a == b,
+                _ => a.name == name && a.artist == artist,
+            });
+            match position {
+                Some(position) => albums[position].tracks.push(track),
+                None => albums.push(Album {
+                    artist,
+                    name,
+                    release_year: meta.and_then(|m| m.release_year),
+                    url,
+                    tracks: vec![track],
+                }),
+            }
+        }
+        for album in albums.iter_mut() {
+            album
+                .tracks
+                .sort_by_key(|t| (t.disc_number, t.track_number));
+        }
+        Self { albums }
+    }
+
+    pub fn from_report(report: &'a Report) -> Self {
+        Self::new(
+            report
+                .entries
+                .iter()
+                .filter(|e| e.status == Status::Unmatched)
+                .map(|e| &e.spotify),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.albums.is_empty()
+    }
+
+    /// Writes `<playlist>.unmatched.csv` and the links of `<playlist>.unmatched.txt`
+    /// to the directory.
+    pub fn write(&self, dir: &str, playlist: &str) -> Result<(), anyhow::Error> {
+        std::fs::create_dir_all(dir)?;
+        let name = file_name(playlist);
+        self.write_csv(&Path::new(dir).join(format!("{}.unmatched.csv", name)))?;
+        self.write_urls(&Path::new(dir).join(format!("{}.unmatched.txt", name)))?;
+        Ok(())
+    }
+
+    pub fn write_csv(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let mut writer = csv::Writer::from_path(path)?;
+        for album in self.albums.iter() {
+            for track in album.tracks.iter() {
+                let meta = spotify(track);
+                writer.serialize(CsvRow {
+                    album_artist: &album.artist,
+                    album: &album.name,
+                    release_year: album.release_year,
+                    album_url: album.url.as_deref(),
+                    disc_number: track.disc_number,
+                    track_number: track.track_number,
+                    artist: artists(track),
+                    title: title(track),
+                    isrc: track.isrc.as_deref(),
+                    url: meta.and_then(|m| m.url.as_deref()),
+                })?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// One spotify link per line, which downloaders like spotdl take as they are.
+    pub fn write_urls(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        for track in self.albums.iter().flat_map(|a| a.tracks.iter()) {
+            if let Some(url) = spotify(track).and_then(|m| m.url.as_ref()) {
+                writeln!(file, "{}", url)?;
+            }
+        }
+        file.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(id: &str, album: &str, album_url: Option<&str>, number: u32) -> TrackAlbumArtist {
+        TrackAlbumArtist {
+            track: id.to_string(),
+            album: album.to_string(),
+            artist: vec!["artist".to_string()],
+            track_number: Some(number),
+            disc_number: Some(1),
+            metadata: MetadataType::Spotify(SpotifyMetadata {
+                uri: id.to_string(),
+                url: Some(format!("https://open.spotify.com/track/{}", id)),
+                album_url: album_url.map(|u| u.to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_group_by_album() {
+        let tracks = [
+            track("a", "first", Some("1"), 5),
+            track("b", "second", None, 1),
+            track("c", "first", Some("1"), 2),
+            track("a", "first", Some("1"), 5),
+            // the same name, but a different release.
+            track("d", "first", Some("2"), 1),
+            track("e", "second", None, 3),
+        ];
+        let unmatched = Unmatched::new(tracks.iter());
+        let albums: Vec<(&str, Vec<&str>)> = unmatched
+            .albums
+            .iter()
+            .map(|a| {
+                (
+                    a.name.as_str(),
+                    a.tracks.iter().map(|t| t.track.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            albums,
+            vec![
+                ("first", vec!["c", "a"]),
+                ("second", vec!["b", "e"]),
+                ("first", vec!["d"]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,336 @@
+use std::{collections::HashSet, time::Duration};
+
+use log::{debug, error, info};
+use rspotify::AuthCodeSpotify;
+use tokio::{
+    net::TcpListener,
+    signal::unix::{signal, Signal, SignalKind},
+    sync::mpsc::{unbounded_channel, UnboundedReceiver},
+    time::Instant,
+};
+
+use crate::{
+    config::PlaylistConfig,
+    plex::{
+        client::Plex,
+        get_library_version,
+        matcher::{index::PlexIndex, pipeline::Pipeline, MatchConfig},
+        webhook, PlexLibrary,
+    },
+    report::Status,
+    spotify::{get_snapshot_id, get_spotify_tracks},
+    state::State,
+    sync::{
+        add_new_matches, download_unmatched, find_matches_and_update_playlist, PlexPlaylist,
+        SyncOptions,
+    },
+    track_album_artist::TrackAlbumArtist,
+};
+
+/// A playlist of the config and the plex playlist it was synced to.
+struct Watched {
+    spotify_id: String,
+    plex_playlist: PlexPlaylist,
+    /// The spotify tracks without a match, known once it was synced by this run.
+    unmatched: Option<Vec<TrackAlbumArtist>>,
+}
+
+/// The scanned plex library and the library version it was scanned at.
+struct Library {
+    version: Option<i64>,
+    scan: PlexLibrary,
+}
+
+pub struct Watcher<'a> {
+    pub plex: &'a Plex,
+    pub spotify: &'a AuthCodeSpotify,
+    pub config: &'a MatchConfig,
+    pub pipeline: &'a Pipeline,
+    pub options: &'a SyncOptions,
+    pub state_file: &'a str,
+    /// Sync every playlist on the first run, changed or not.
+    pub force: bool,
+    /// Address to listen on for plex webhooks, e.g. `0.0.0.0:8585`.
+    pub webhook: Option<String>,
+    /// The `token` query parameter the webhook requests must carry.
+    pub webhook_token: String,
+}
+
+impl Watcher<'_> {
+    /// Syncs the playlists every `interval` until SIGTERM or ctrl-c. Playlists are only
+    /// matched again when they changed on spotify or the plex library changed since
+    /// their last sync, also by an earlier run, and the library is only scanned again
+    /// when it changed. With the webhook, tracks added to plex in between are matched
+    /// right away.
+    pub async fn run(
+        &self,
+        playlists: &[PlaylistConfig],
+        interval: Duration,
+    ) -> Result<(), anyhow::Error> {
+        let mut state = State::load(self.state_file)?;
+        let mut watched: Vec<Watched> = playlists
+            .iter()
+            .map(|p| Watched {
+                spotify_id: p.id.clone(),
+                plex_playlist: PlexPlaylist {
+                    name: p.name.clone(),
+                    id: state.plex_playlist_id(&p.id),
+                },
+                unmatched: None,
+            })
+            .collect();
+        let mut library: Option<Library> = None;
+        let mut force = self.force;
+        let mut terminate = signal(SignalKind::terminate())?;
+        let mut new_tracks = match &self.webhook {
+            Some(address) => {
+                let listener = TcpListener::bind(address).await?;
+                let (sender, receiver) = unbounded_channel();
+                let token = self.webhook_token.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = webhook::serve(listener, sender, &token).await {
+                        error!("Plex webhook listener failed: {:#}", err);
+                    }
+                });
+                Some(receiver)
+            }
+            None => None,
+        };
+
+        loop {
+            // a sync can take long, e.g. waiting for plex to scan the downloads, so it is
+            // interrupted too. What was synced so far is already saved.
+            tokio::select! {
+                result = self.sync_changed(&mut watched, &mut library, &mut state, force) => {
+                    if let Err(err) = result {
+                        error!("Sync failed: {:#}", err);
+                    }
+                }
+                _ = shutdown(&mut terminate) => return self.stop(),
+            }
+            force = false;
+            info!("Next sync in {:?}", interval);
+            let next_sync = Instant::now() + interval;
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(next_sync) => break,
+                    _ = shutdown(&mut terminate) => return self.stop(),
+                    Some(artist) = next_artist(&mut new_tracks) => {
+                        let mut artists = HashSet::from([artist]);
+                        // an album sends one event per track.
+                        while let Some(Ok(artist)) = new_tracks.as_mut().map(|r| r.try_recv()) {
+                            artists.insert(artist);
+                        }
+                        tokio::select! {
+                            result = self.match_new_tracks(
+                                &artists,
+                                &mut watched,
+                                &mut library,
+                                &mut state,
+                            ) => {
+                                if let Err(err) = result {
+                                    error!("Matching the new tracks failed: {:#}", err);
+                                }
+                            }
+                            _ = shutdown(&mut terminate) => return self.stop(),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn stop(&self) -> Result<(), anyhow::Error> {
+        info!("Stopped watching");
+        Ok(())
+    }
+
+    async fn sync_changed(
+        &self,
+        watched: &mut [Watched],
+        library: &mut Option<Library>,
+        state: &mut State,
+        force: bool,
+    ) -> Result<(), anyhow::Error> {
+        let mut version = get_library_version(self.plex).await?;
+        // without a version there is no telling whether it changed.
+        let mut library_changed = match library {
+            Some(library) => version.is_none() || library.version != version,
+            None => true,
+        };
+        let mut index: Option<PlexIndex> = None;
+
+        for playlist in watched.iter_mut() {
+            let snapshot_id = match get_snapshot_id(self.spotify, &playlist.spotify_id) {
+                Ok(snapshot_id) => snapshot_id,
+                Err(err) => {
+                    error!("Checking {} failed: {:#}", playlist.plex_playlist.name, err);
+                    continue;
+                }
+            };
+            if !force && state.is_synced(&playlist.spotify_id, &snapshot_id, version) {
+                debug!("{} is unchanged", playlist.plex_playlist.name);
+                continue;
+            }
+
+            let plex_tracks = match index {
+                Some(ref index) => index,
+                None => {
+                    if library_changed {
+                        *library = Some(Library {
+                            version,
+                            scan: PlexLibrary::scan(self.plex).await?,
+                        });
+                        library_changed = false;
+                    }
+                    let tracks = library.as_ref().unwrap().scan.tracks();
+                    index.insert(PlexIndex::new(tracks, self.config))
+                }
+            };
+            match self.sync(playlist, plex_tracks).await {
+                Ok(()) => {
+                    if self.options.download_command.is_some() {
+                        let library = library.as_mut().unwrap();
+                        match self.download(playlist, library).await {
+                            Ok(()) => {
+                                // the downloads changed the library, the playlists that
+                                // follow are synced with it.
+                                version = library.version;
+                                // built before the downloads.
+                                index = None;
+                            }
+                            Err(err) => error!(
+                                "Downloading for {} failed: {:#}",
+                                playlist.plex_playlist.name, err
+                            ),
+                        }
+                    }
+                    state.synced(
+                        &playlist.spotify_id,
+                        &snapshot_id,
+                        version,
+                        &playlist.plex_playlist.id,
+                    );
+                    state.save(self.state_file)?;
+                }
+                Err(err) => error!("Syncing {} failed: {:#}", playlist.plex_playlist.name, err),
+            }
+        }
+        Ok(())
+    }
+
+    async fn sync(
+        &self,
+        playlist: &mut Watched,
+        plex_tracks: &PlexIndex,
+    ) -> Result<(), anyhow::Error> {
+        info!("Syncing {}", playlist.plex_playlist.name);
+        let spotify_tracks = get_spotify_tracks(self.spotify, &playlist.spotify_id).await?;
+        let report = find_matches_and_update_playlist(
+            self.plex,
+            &spotify_tracks,
+            plex_tracks,
+            &mut playlist.plex_playlist,
+            self.config,
+            self.pipeline,
+            self.options,
+        )
+        .await?;
+        playlist.unmatched = Some(
+            report
+                .entries
+                .into_iter()
+                .filter(|e| e.status == Status::Unmatched)
+                .map(|e| e.spotify)
+                .collect(),
+        );
+        Ok(())
+    }
+
+    /// Downloads the unmatched tracks of the playlist and adds the ones plex found.
+    async fn download(
+        &self,
+        playlist: &mut Watched,
+        library: &mut Library,
+    ) -> Result<(), anyhow::Error> {
+        let unmatched = playlist.unmatched.clone().unwrap_or_default();
+        playlist.unmatched = Some(
+            download_unmatched(
+                self.plex,
+                &mut library.scan,
+                unmatched,
+                &mut playlist.plex_playlist,
+                self.config,
+                self.pipeline,
+                self.options,
+            )
+            .await?,
+        );
+        library.version = get_library_version(self.plex).await?;
+        Ok(())
+    }
+
+    /// Scans the artists again and matches only the tracks that were unmatched, the
+    /// ones found are added at the end of their playlist. Only a full sync counts as
+    /// synced with the current library.
+    async fn match_new_tracks(
+        &self,
+        artists: &HashSet<String>,
+        watched: &mut [Watched],
+        library: &mut Option<Library>,
+        state: &mut State,
+    ) -> Result<(), anyhow::Error> {
+        // not scanned yet, the next sync scans everything anyway.
+        let Some(library) = library else {
+            return Ok(());
+        };
+        for artist in artists.iter() {
+            library.scan.rescan_artist(self.plex, artist).await?;
+        }
+        // only the artists were scanned again, the library version is left behind so
+        // that the next sync still picks up changes the webhook didn't report.
+        let plex_tracks = PlexIndex::new(library.scan.tracks(), self.config);
+
+        for playlist in watched.iter_mut() {
+            let Some(unmatched) = playlist.unmatched.as_mut() else {
+                continue;
+            };
+            add_new_matches(
+                self.plex,
+                &plex_tracks,
+                unmatched,
+                &mut playlist.plex_playlist,
+                self.config,
+                self.pipeline,
+            )
+            .await?;
+            if let Some(synced) = state.playlists.get(&playlist.spotify_id).cloned() {
+                // a playlist created by the new matches is refilled by the next sync.
+                state.synced(
+                    &playlist.spotify_id,
+                    &synced.snapshot_id,
+                    synced.library_version,
+                    &playlist.plex_playlist.id,
+                );
+            }
+        }
+        state.save(self.state_file)?;
+        Ok(())
+    }
+}
+
+/// SIGTERM or ctrl-c.
+async fn shutdown(terminate: &mut Signal) {
+    tokio::select! {
+        _ = terminate.recv() => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+}
+
+/// The next artist with new tracks, never when there is no webhook.
+async fn next_artist(receiver: &mut Option<UnboundedReceiver<String>>) -> Option<String> {
+    match receiver {
+        Some(receiver) => receiver.recv().await,
+        None => std::future::pending().await,
+    }
+}